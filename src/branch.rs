@@ -1,22 +1,28 @@
-use std::collections::HashMap;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
 use std::env::consts;
 use std::fmt::Display;
+use std::fs;
+use std::io::Write;
 use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio};
+use std::process::{Command, Output, Stdio};
 use std::str::FromStr;
 use std::time::SystemTime;
-use std::{env, fs};
 
 use anyhow::{bail, Context, Result};
-use chrono::{Date, Datelike, Local, Month, NaiveDate, TimeZone};
+use chrono::{Datelike, NaiveDate};
 use handlebars::Handlebars;
-use num_traits::FromPrimitive;
-use ordinal::Ordinal;
 use serde_json::json;
+use strum::VariantNames;
 use walkdir::WalkDir;
 
-use crate::conf::{Class, Config, DocumentType, Format};
-use crate::locations::{self, files, folders};
+use crate::conf::{Bibliography, Class, Config, DocumentType, Engine, Format, Target, Term};
+use crate::diagnostic::{self, BuildError, Diagnostic, Severity};
+use crate::error::{Error, Kind};
+use crate::header::{Header, Metadata};
+use crate::html;
+use crate::layout::{Fields, Layout};
+use crate::locations::{files, folders};
 use crate::template::{BranchTemplate, RootTemplate};
 
 #[derive(Debug, PartialEq)]
@@ -27,11 +33,17 @@ pub struct Branch {
 	pub class: Class,
 	pub path: PathBuf,
 	pub pdf_path: PathBuf,
+	pub html_path: PathBuf,
+	pub docx_path: PathBuf,
 	pub imgs_dir: PathBuf,
+	pub build_dir: PathBuf,
 	pub branch_template: Option<BranchTemplate>,
 	pub root_template: RootTemplate,
-	pub creation_time: Date<Local>,
+	pub creation_time: NaiveDate,
 	pub mod_time: SystemTime,
+	pub bibliography: Option<Bibliography>,
+	pub metadata: Metadata,
+	pub archived: Option<String>,
 }
 
 impl Branch {
@@ -43,25 +55,27 @@ impl Branch {
 		class: Class,
 		branch_template: Option<BranchTemplate>,
 		root_template: RootTemplate,
-		creation_time: Date<Local>,
+		creation_time: NaiveDate,
 		mod_time: SystemTime,
+		layout: &Layout,
 	) -> Result<Self> {
-		let month_name = Month::from_u32(creation_time.month()).unwrap().name();
+		let fields = Fields {
+			class: &class.name,
+			doc_type: &doc_type,
+			name: &name,
+			created: creation_time,
+		};
+		let relative = layout.branch(&fields);
+		let file = |folder: &str, extension: &str| {
+			PathBuf::from(folder).join(format!("{}{}", relative.display(), extension))
+		};
 		Ok(Branch {
-			path: PathBuf::from(locations::folders::BRANCHES)
-				.join(&class.name)
-				.join(month_name)
-				.join(doc_type.to_string())
-				.join(format!("{}{}", name, format.extension())),
-			pdf_path: PathBuf::from(locations::folders::PDFS)
-				.join(&class.name)
-				.join(month_name)
-				.join(doc_type.to_string())
-				.join(format!("{}.pdf", name)),
-			imgs_dir: PathBuf::from(locations::folders::IMAGES)
-				.join(&class.name)
-				.join(month_name)
-				.join(&name),
+			path: file(folders::BRANCHES, format.extension()),
+			pdf_path: file(folders::PDFS, ".pdf"),
+			html_path: file(folders::HTML, ".html"),
+			docx_path: file(folders::DOCX, ".docx"),
+			imgs_dir: PathBuf::from(folders::IMAGES).join(layout.imgs(&fields)),
+			build_dir: PathBuf::from(folders::BUILD).join(&relative),
 			name,
 			format,
 			doc_type,
@@ -70,44 +84,68 @@ impl Branch {
 			root_template,
 			creation_time,
 			mod_time,
+			bibliography: None,
+			metadata: Metadata::default(),
+			archived: None,
 		})
 	}
 
+	pub fn in_term(&self, term: &Term) -> bool {
+		match &self.archived {
+			Some(archived) => archived == &term.name,
+			None => {
+				self.class.terms.contains(&term.name)
+					&& (self.class.terms.len() == 1 || term.contains(self.creation_time))
+			}
+		}
+	}
+
+	pub fn relocated(&self, class: &Class, layout: &Layout) -> Result<Self> {
+		let moved = Self::new(
+			self.name.clone(),
+			self.format.clone(),
+			self.doc_type,
+			class.clone(),
+			None,
+			self.root_template.clone(),
+			self.creation_time,
+			self.mod_time,
+			layout,
+		)?;
+		Ok(match &self.archived {
+			Some(term) => moved.into_archive(term),
+			None => moved,
+		})
+	}
+
+	pub fn into_archive(mut self, term: &str) -> Self {
+		let root = Term::folder(term);
+		for path in [
+			&mut self.path,
+			&mut self.pdf_path,
+			&mut self.html_path,
+			&mut self.docx_path,
+			&mut self.imgs_dir,
+			&mut self.build_dir,
+		] {
+			*path = root.join(&path);
+		}
+		self.archived = Some(term.to_string());
+		self
+	}
+
 	pub fn inject(
 		&self,
 		config: &Config,
 		template_content: String,
 		branch_content: Option<String>,
 	) -> Result<String> {
-		fn custom_escape(s: &str, format: &Format) -> String {
-			if *format == Format::Markdown {
-				return s.to_string();
-			}
-			let mut output = String::new();
-			for (i, c) in s.chars().enumerate() {
-				if s.chars().nth(i - 1).unwrap_or_default().to_string() == *"\\" {
-					output.push(c);
-					continue;
-				}
-				match c {
-					'&' => output.push_str("\\&"),
-					'$' => output.push_str("\\$"),
-					'#' => output.push_str("\\#"),
-					'%' => output.push_str("\\%"),
-					_ => output.push(c),
-				}
-			}
-			output
-		}
-
-		let ordinal_suffix = Ordinal(self.creation_time.day()).suffix();
-		let format = if (self.format == Format::Markdown && branch_content.is_some())
-			|| self.format == Format::LaTeX
-		{
-			Format::LaTeX
-		} else {
-			Format::Markdown
+		let plugin = match branch_content {
+			Some(_) => self.format.plugin().root_format().plugin(),
+			None => self.format.plugin(),
 		};
+		let bibliography = self.bibliography(config);
+		let (files, csl) = build_names(&bibliography)?;
 		let mut reg = Handlebars::new();
 		reg.register_escape_fn(handlebars::no_escape);
 
@@ -118,16 +156,13 @@ impl Branch {
 					"simple_date": self.creation_time.format("%F").to_string(),
 					"day": self.creation_time.day(),
 					"year": self.creation_time.year(),
-					"date": match format {
-						Format::Markdown => self.creation_time.format(&format!("%A, %B %e^{}^, %Y", ordinal_suffix)).to_string(),
-						Format::LaTeX => self.creation_time.format(&format!("%A, %B %e\\textsuperscript{{{}}}, %Y", ordinal_suffix)).to_string()
-					},
+					"date": plugin.date(self.creation_time),
 					"month": self.creation_time.format("%B").to_string()
 				},
 				"author": config.author,
-				"name": custom_escape(&self.name, &format),
+				"name": plugin.escape(&self.name),
 				"class": {
-					"name": custom_escape(&self.class.name, &format),
+					"name": plugin.escape(&self.class.name),
 					"teacher": self.class.teacher,
 				},
 				"root": {
@@ -136,183 +171,626 @@ impl Branch {
 				"branch": {
 					"content": branch_content.unwrap_or_default(),
 				},
+				"bibliography": {
+					"files": files,
+					"names": files.iter().map(|f| f.rsplit_once('.').map_or(f.as_str(), |(stem, _)| stem)).collect::<Vec<&str>>().join(","),
+					"csl": csl,
+				},
 				"type": self.doc_type.to_string(),
+				"metadata": self.metadata,
 				"required_preamble": include_str!("required_preamble.tex"),
-				"imgs_dir": format!("{{{}/}}", custom_escape(Path::new("..").join(&self.imgs_dir).to_str().unwrap(), &format))
+				"imgs_dir": plugin.imgs_dir(self),
 			}),
 		)?)
 	}
 
 	pub fn parse(path: PathBuf, config: &Config) -> Result<Self> {
 		let format = Format::from_path(&path).unwrap();
-		let mut data = HashMap::new();
-		for line in fs::read_to_string(&path)
-			.context(format!("Failed to read contents of {}", path.display()))?
-			.lines()
-		{
-			let trimmed_line = line.trim();
-			let raw_chunks = trimmed_line.split_once(&config.delimiter);
-			if raw_chunks.is_none() {
-				continue;
-			}
-			let chunks = raw_chunks.unwrap();
-			data.insert(chunks.0.trim().to_string(), chunks.1.trim().to_string());
-			if format == Format::Markdown && trimmed_line.starts_with("-->")
-				|| format == Format::LaTeX && trimmed_line.starts_with("\\fi")
-			{
-				break;
+		let header = Header::parse(
+			&fs::read_to_string(&path)
+				.context(format!("Failed to read contents of {}", path.display()))?,
+			&format,
+			&config.delimiter,
+		)
+		.context(format!("Failed to parse header of {}", path.display()))?;
+
+		let invalid = || {
+			Error::new(
+				Kind::Config,
+				format!("{} is not a valid UTF-8 path", path.display()),
+			)
+			.hint("rename it using only UTF-8 characters")
+		};
+		let (archived, relative) = match path.strip_prefix(folders::ARCHIVE) {
+			Ok(rest) => {
+				let term = rest
+					.iter()
+					.next()
+					.and_then(|t| t.to_str())
+					.with_context(invalid)?;
+				(
+					Some(term.to_string()),
+					rest.strip_prefix(term)?.strip_prefix(folders::BRANCHES)?,
+				)
 			}
-		}
-		let required_keys = ["created", "root"];
-		for key in required_keys {
-			if !data.contains_key(key) {
-				bail!("{} is missing required key: {}", path.display(), key);
+			Err(_) => (None, path.strip_prefix(folders::BRANCHES).unwrap_or(&path)),
+		};
+		let relative = Path::new(
+			relative
+				.to_str()
+				.and_then(|r| r.strip_suffix(format.extension()))
+				.with_context(invalid)?,
+		);
+		let fields = [&config.layout, &Layout::default()]
+			.iter()
+			.find_map(|l| l.matches(relative))
+			.unwrap_or_default();
+		let unplaced = |field: &str| {
+			Error::new(
+				Kind::Config,
+				format!("No {} in the header or path of {}", field, path.display()),
+			)
+			.hint(format!(
+				"move it to match the layout {} (types are {}) or set `{}` in its header",
+				config.layout,
+				DocumentType::VARIANTS.join(", "),
+				field
+			))
+		};
+		let class_name = header
+			.class
+			.as_ref()
+			.or_else(|| fields.get("class"))
+			.with_context(|| unplaced("class"))?;
+		let doc_type = match header.doc_type {
+			Some(doc_type) => doc_type,
+			None => {
+				DocumentType::from_str(fields.get("type").with_context(|| unplaced("type"))?)
+					.context(format!("Failed to pair document type {}", &path.display()))?
 			}
-		}
+		};
 
-		let path_chunks = path.iter().rev();
-		let doc_type = path_chunks.clone().nth(1).unwrap().to_str().unwrap();
-		let class_name = path_chunks.clone().nth(3).unwrap().to_str().unwrap();
+		let name = match fields.get("name") {
+			Some(name) => name.clone(),
+			None => {
+				relative
+					.file_name()
+					.and_then(|n| n.to_str())
+					.with_context(invalid)?
+					.to_string()
+			}
+		};
 
-		Self::new(
-			path.file_name()
-				.unwrap()
-				.to_str()
-				.unwrap()
-				.strip_suffix(format.extension())
-				.unwrap()
-				.to_string(),
+		let mut branch = Self::new(
+			name,
 			format,
-			DocumentType::from_str(doc_type)
-				.context(format!("Failed to pair document type {}", &path.display()))?,
-			config
+			doc_type,
+			match config
 				.classes
 				.iter()
-				.find(|c| c.name == class_name)
-				.unwrap()
-				.clone(),
+				.find(|c| &c.name == class_name)
+				.or_else(|| {
+					config
+						.classes
+						.iter()
+						.find(|c| c.renamed_from.contains(class_name))
+				}) {
+				Some(class) => class.clone(),
+				// Archived branches outlive the classes they were written for
+				None if archived.is_some() => {
+					Class {
+						name: class_name.clone(),
+						..Class::default()
+					}
+				}
+				None => {
+					return Err(Error::new(
+						Kind::Config,
+						format!(
+							"{} (used by {}) is not a class in {}",
+							class_name,
+							path.display(),
+							files::CONFIG
+						),
+					)
+					.hint(format!(
+						"add a [[classes]] entry named {} to {}",
+						class_name,
+						files::CONFIG
+					))
+					.into())
+				}
+			},
 			None,
-			RootTemplate::from_filename(&format!("{}.hbs", data.get("root").unwrap())),
-			Local
-				.from_local_date(
-					&NaiveDate::parse_from_str(data.get("created").unwrap(), "%F").context(
-						format!("Failed to parse created date from {}", path.display()),
-					)?,
-				)
-				.unwrap(),
-			fs::metadata(path)?.modified()?,
-		)
+			RootTemplate::from_filename(&format!("{}.hbs", header.root)),
+			header.created,
+			fs::metadata(&path)?.modified()?,
+			&config.layout,
+		)?;
+		if let Some(term) = archived {
+			branch = branch.into_archive(&term);
+		}
+		branch.path = path;
+		// Like the ones in optic.toml, these paths are relative to the project root
+		if !header.bibliography.is_empty() || header.csl.is_some() {
+			branch.bibliography = Some(Bibliography {
+				files: header.bibliography,
+				csl: header.csl,
+			});
+		}
+		branch.metadata = header.metadata;
+		Ok(branch)
 	}
 
-	pub fn get_all(config: &Config) -> Result<Vec<Self>> {
+	pub fn bibliography(&self, config: &Config) -> Bibliography {
+		let levels = [
+			self.bibliography.as_ref(),
+			self.class.bibliography.as_ref(),
+			config.bibliography.as_ref(),
+		];
+		Bibliography {
+			files: levels
+				.iter()
+				.flatten()
+				.map(|b| &b.files)
+				.find(|f| !f.is_empty())
+				.cloned()
+				.unwrap_or_default(),
+			csl: levels.iter().flatten().find_map(|b| b.csl.clone()),
+		}
+	}
+
+	pub fn get_all(config: &Config) -> Result<Vec<Self>> { Self::collect(config, false) }
+
+	// Archived branches are left out of everyday commands and only show up when asked for
+	pub fn get_all_with_archive(config: &Config) -> Result<Vec<Self>> {
+		Self::collect(config, true)
+	}
+
+	fn collect(config: &Config, archived: bool) -> Result<Vec<Self>> {
 		let mut branches: Vec<Self> = Vec::new();
-		for entry in WalkDir::new(folders::BRANCHES) {
+		for path in Self::paths(archived)? {
+			branches.push(
+				Self::parse(path.clone(), config)
+					.context(format!("Failed to parse {}", path.display()))?,
+			)
+		}
+		branches.sort_by_key(|b| Reverse(b.mod_time));
+		Ok(branches)
+	}
+
+	pub fn paths(archived: bool) -> Result<Vec<PathBuf>> {
+		let mut roots = vec![PathBuf::from(folders::BRANCHES)];
+		if let (true, Ok(terms)) = (archived, fs::read_dir(folders::ARCHIVE)) {
+			roots.extend(
+				terms
+					.flatten()
+					.map(|t| t.path().join(folders::BRANCHES))
+					.filter(|r| r.exists()),
+			);
+		}
+
+		let mut paths = Vec::new();
+		for entry in roots.iter().flat_map(WalkDir::new) {
 			let entry = entry.context("Failed to open walk dir entry")?;
-			let extension = Format::from_path(entry.path());
-			let path = entry.path();
-			if entry.file_type().is_file() && extension.is_some() {
-				branches.push(
-					Self::parse(path.to_path_buf(), config)
-						.context(format!("Failed to parse {}", path.display()))?,
-				)
+			if entry.file_type().is_file() && Format::from_path(entry.path()).is_some() {
+				paths.push(entry.into_path());
 			}
 		}
-		branches.sort_by(|a, b| b.mod_time.cmp(&a.mod_time));
-		Ok(branches)
+		Ok(paths)
 	}
 
-	pub fn build(&self, config: &Config, latexmk: &bool) -> Result<()> {
-		let mut branch_content = fs::read_to_string(&self.path)?;
-		let build_engine = if *latexmk { "latexmk" } else { "pdflatex" };
-		if self.format == Format::Markdown {
-			branch_content = String::from_utf8(
-				Command::new("pandoc")
-					.arg("-r")
-					.arg("markdown-auto_identifiers")
-					.arg("-w")
-					.arg("latex")
-					.arg("--pdf-engine")
-					.arg(build_engine)
-					.arg(&self.path.to_str().unwrap())
-					.stdout(Stdio::piped())
-					.output()?
-					.stdout,
-			)?;
+	pub fn engine(&self, config: &Config, engine: Option<&Engine>) -> Result<Engine> {
+		if let Some(engine) = engine {
+			return Ok(engine.clone());
 		}
+		if let Some(engine) = Engine::from_magic_comment(
+			&fs::read_to_string(&self.root_template.path)
+				.context("Failed to read root template")?,
+		) {
+			return engine.context(format!(
+				"Invalid engine in magic comment of {}",
+				self.root_template.path.display()
+			));
+		}
+		Ok(self.class.engine.clone().unwrap_or_else(|| config.engine()))
+	}
+
+	pub fn targets(&self, config: &Config) -> Vec<Target> {
+		self.class
+			.outputs
+			.as_ref()
+			.or_else(|| config.type_outputs.get(&self.doc_type.to_string()))
+			.unwrap_or(&config.outputs)
+			.clone()
+	}
+
+	pub fn output_path(&self, target: &Target) -> &PathBuf {
+		match target {
+			Target::Pdf => &self.pdf_path,
+			Target::Html => &self.html_path,
+			Target::Docx => &self.docx_path,
+		}
+	}
+
+	pub fn build(&self, config: &Config, engine: Option<&Engine>) -> Result<()> {
+		for target in self.targets(config) {
+			self.build_target(config, &target, engine)?;
+		}
+		Ok(())
+	}
+
+	pub fn build_target(
+		&self,
+		config: &Config,
+		target: &Target,
+		engine: Option<&Engine>,
+	) -> Result<()> {
+		match target {
+			Target::Pdf => self.build_pdf(config, engine),
+			Target::Html => self.build_html(config),
+			Target::Docx => self.build_docx(config),
+		}
+	}
+
+	pub fn build_html(&self, config: &Config) -> Result<()> {
+		let content = self.format.plugin().to_html(self, config)?;
+		let html = html::standalone(
+			self,
+			config,
+			&html::inline_images(&content, &self.imgs_dir).context("Failed to inline images")?,
+		)?;
+
+		fs::create_dir_all(self.html_path.parent().unwrap())
+			.context("Failed to create HTML's folder")?;
+		fs::write(&self.html_path, html).context("Failed to write HTML file")?;
+		Ok(())
+	}
+
+	pub fn build_docx(&self, config: &Config) -> Result<()> {
+		fs::create_dir_all(self.docx_path.parent().unwrap())
+			.context("Failed to create DOCX's folder")?;
+		let mut args = vec![
+			"--resource-path",
+			self.imgs_dir.to_str().unwrap(),
+			"-o",
+			self.docx_path.to_str().unwrap(),
+		];
+		if let Some(reference_doc) = &self.class.reference_doc {
+			args.push("--reference-doc");
+			args.push(reference_doc.to_str().unwrap());
+		}
+		self.pandoc(&self.bibliography(config), "docx", &args)
+			.context("Failed to convert to DOCX")?;
+		Ok(())
+	}
+
+	pub fn build_pdf(&self, config: &Config, engine: Option<&Engine>) -> Result<()> {
+		if self.format.plugin().root_format() == Format::Typst {
+			return self.build_typst(config);
+		}
+		let branch_content = self.format.plugin().to_latex(self, config)?;
+		let engine = self.engine(config, engine)?;
+		let bibliography = self.bibliography(config);
 
 		let latex = self.inject(
 			config,
 			fs::read_to_string(&self.root_template.path)?,
-			Some(branch_content),
+			Some(branch_content.clone()),
 		)?;
 
-		if Path::new(folders::BUILD).exists() {
-			fs::remove_dir_all(folders::BUILD)?;
+		self.prepare_build_dir(files::LATEX_BUILD, &latex, &bibliography)?;
+
+		let mut build_output = self.run_engine(&engine)?;
+		let mut bibliography_output = None;
+		if engine.multi_pass() && build_output.status.success() {
+			let mut needs_rerun = false;
+			if let Some(tool) = bibliography_tool(&latex) {
+				let output = Command::new(tool)
+					.arg(files::LATEX_BUILD.strip_suffix(".tex").unwrap())
+					.current_dir(&self.build_dir)
+					.stdout(Stdio::piped())
+					.stderr(Stdio::piped())
+					.output()
+					.context(format!("Failed to run {}", tool))?;
+				// Missing fields or citations make these exit non-zero, but the engine can still
+				// produce a PDF
+				bibliography_output = Some((tool, output));
+				needs_rerun = true;
+			}
+
+			let mut previous_aux = None;
+			let mut reruns = 0;
+			while build_output.status.success() {
+				let aux = fs::read_to_string(self.build_dir.join(files::AUX_BUILD)).ok();
+				let aux_changed = match &previous_aux {
+					Some(previous) => aux.as_ref() != Some(previous),
+					None => aux.as_deref().is_some_and(aux_has_content),
+				};
+				let log_requests_rerun = fs::read_to_string(self.build_dir.join(files::LOG_BUILD))
+					.is_ok_and(|log| log.contains("Rerun to get"));
+				if !(needs_rerun || aux_changed || log_requests_rerun)
+					|| reruns >= config.max_reruns
+				{
+					break;
+				}
+
+				previous_aux = aux;
+				needs_rerun = false;
+				reruns += 1;
+				build_output = self.run_engine(&engine)?;
+			}
+		}
+		if !build_output.status.success() {
+			let log = self.build_dir.join(files::FAIL_LOG);
+			let mut log_content = [build_output.stdout, build_output.stderr].concat();
+			if let Some((_, output)) = &bibliography_output {
+				log_content.extend([&output.stdout[..], &output.stderr[..]].concat());
+			}
+			fs::write(&log, log_content).context("Failed to write to log file")?;
+
+			let tex_log = match fs::read_to_string(self.build_dir.join(files::LOG_BUILD)) {
+				Ok(tex_log) => tex_log,
+				Err(_) => fs::read_to_string(&log)?,
+			};
+			let content_offset = if self.format.plugin().root_format() == self.format {
+				latex
+					.find(&branch_content)
+					.map(|i| latex[..i].matches('\n').count())
+			} else {
+				None
+			};
+			let source = fs::read_to_string(&self.path)?;
+			let mut diagnostics = diagnostic::parse(&tex_log);
+			for diagnostic in &mut diagnostics {
+				diagnostic.resolve(&latex, &source, content_offset);
+			}
+			diagnostics.extend(
+				bibliography_output
+					.as_ref()
+					.and_then(|(tool, output)| bibliography_warning(tool, output)),
+			);
+			return Err(BuildError { log, diagnostics }.into());
 		}
-		fs::create_dir(folders::BUILD)
-			.context("Failed to create temporary directory for building")?;
-		env::set_current_dir(folders::BUILD)
-			.context("Failed to enter temporary directory to build")?;
-		fs::write(files::LATEX_BUILD, latex).context("Failed to write to build LaTeX file")?;
 
-		let build_output = Command::new(build_engine)
-			.arg(files::LATEX_BUILD)
-			.arg(if *latexmk { "-pdf" } else { "" })
+		self.store_pdf()
+	}
+
+	fn build_typst(&self, config: &Config) -> Result<()> {
+		let branch_content = fs::read_to_string(&self.path)?;
+		let typst = self.inject(
+			config,
+			fs::read_to_string(&self.root_template.path)?,
+			Some(branch_content.clone()),
+		)?;
+		self.prepare_build_dir(files::TYPST_BUILD, &typst, &self.bibliography(config))?;
+
+		let build_output = Command::new("typst")
+			.arg("compile")
+			.arg("--root")
+			.arg(self.relative_root())
+			.arg(files::TYPST_BUILD)
+			.arg(files::PDF_BUILD)
+			.current_dir(&self.build_dir)
 			.stdout(Stdio::piped())
-			.output()?;
+			.stderr(Stdio::piped())
+			.output()
+			.context("Failed to run typst")?;
 		if !build_output.status.success() {
-			env::set_current_dir("..")?;
-			fs::write(files::FAIL_LOG, build_output.stdout)
+			let log = self.build_dir.join(files::FAIL_LOG);
+			fs::write(&log, [build_output.stdout, build_output.stderr].concat())
 				.context("Failed to write to log file")?;
-			bail!(
-				"Failed to generate PDF. Please check {} in {}",
-				files::FAIL_LOG,
-				folders::BUILD
-			);
+
+			let content_offset = typst
+				.find(&branch_content)
+				.map(|i| typst[..i].matches('\n').count());
+			let mut diagnostics = diagnostic::parse_typst(&fs::read_to_string(&log)?);
+			for diagnostic in &mut diagnostics {
+				diagnostic.resolve(&typst, &branch_content, content_offset);
+			}
+			return Err(BuildError { log, diagnostics }.into());
+		}
+
+		self.store_pdf()
+	}
+
+	fn prepare_build_dir(
+		&self,
+		filename: &str,
+		content: &str,
+		bibliography: &Bibliography,
+	) -> Result<()> {
+		if self.build_dir.exists() {
+			fs::remove_dir_all(&self.build_dir)?;
+		}
+		fs::create_dir_all(&self.build_dir)
+			.context("Failed to create temporary directory for building")?;
+		fs::write(self.build_dir.join(filename), content)
+			.context(format!("Failed to write to build file {}", filename))?;
+		let (files, csl) = build_names(bibliography)?;
+		for (file, name) in bibliography
+			.files
+			.iter()
+			.zip(&files)
+			.chain(bibliography.csl.iter().zip(&csl))
+		{
+			fs::copy(file, self.build_dir.join(name)).context(format!(
+				"Failed to copy {} into the build directory",
+				file.display()
+			))?;
 		}
+		Ok(())
+	}
 
-		env::set_current_dir("..")?;
+	fn store_pdf(&self) -> Result<()> {
 		fs::create_dir_all(self.pdf_path.parent().unwrap())
 			.context("Failed to create PDF's folder")?;
-		fs::rename(
-			PathBuf::from(folders::BUILD).join(files::PDF_BUILD),
-			&self.pdf_path,
-		)
-		.context("Failed to move output PDF to permanent location")?;
+		fs::rename(self.build_dir.join(files::PDF_BUILD), &self.pdf_path)
+			.context("Failed to move output PDF to permanent location")?;
 
-		fs::remove_dir_all(folders::BUILD)?;
+		self.clean_build_dir()
+			.context("Failed to remove temporary build directory")?;
 
 		Ok(())
 	}
 
+	pub fn pandoc(
+		&self,
+		bibliography: &Bibliography,
+		writer: &str,
+		args: &[&str],
+	) -> Result<String> {
+		let source = self.format.plugin().source(&self.path)?;
+		let mut pandoc = Command::new("pandoc");
+		pandoc
+			.arg("-r")
+			.arg(self.format.plugin().pandoc_reader())
+			.arg("-w")
+			.arg(writer)
+			.args(args);
+		if !bibliography.files.is_empty() {
+			pandoc.arg("--citeproc");
+			for file in &bibliography.files {
+				pandoc.arg("--bibliography").arg(file);
+			}
+			if let Some(csl) = &bibliography.csl {
+				pandoc.arg("--csl").arg(csl);
+			}
+		}
+		let mut child = pandoc
+			.arg("-")
+			.stdin(Stdio::piped())
+			.stdout(Stdio::piped())
+			.stderr(Stdio::piped())
+			.spawn()
+			.with_context(|| {
+				match self.format {
+					Format::Markdown => {
+						"Failed to run pandoc. Install it or set markdown_converter = \"native\" \
+						 in optic.toml"
+					}
+					_ => "Failed to run pandoc. Install it to build this branch",
+				}
+			})?;
+		child
+			.stdin
+			.take()
+			.unwrap()
+			.write_all(source.as_bytes())
+			.context("Failed to pass branch to pandoc")?;
+		let output = child.wait_with_output()?;
+		if !output.status.success() {
+			bail!(
+				"pandoc exited with {}: {}",
+				output.status,
+				String::from_utf8_lossy(&output.stderr).trim()
+			);
+		}
+		String::from_utf8(output.stdout).context("pandoc produced invalid UTF-8")
+	}
+
+	fn relative_root(&self) -> PathBuf { self.build_dir.iter().map(|_| "..").collect() }
+
+	// Builds run inside build_dir, so images are reached by climbing back out of it
+	pub fn relative_imgs_dir(&self) -> PathBuf { self.relative_root().join(&self.imgs_dir) }
+
+	// Points references to this branch's images at the moved branch's images instead. A match has
+	// to end the directory name so imgs/Lab isn't rewritten inside imgs/Lab 2
+	pub fn move_imgs_refs(&self, content: &str, moved: &Self) -> String {
+		let plugin = self.format.plugin();
+		let (old, new) = (
+			self.imgs_dir.to_str().unwrap(),
+			moved.imgs_dir.to_str().unwrap(),
+		);
+		let mut content = content.to_string();
+		let mut pairs = vec![(old.to_string(), new.to_string())];
+		if plugin.escape(old) != old {
+			pairs.push((plugin.escape(old), plugin.escape(new)));
+		}
+		for (old, new) in pairs {
+			let mut output = String::new();
+			let mut rest = content.as_str();
+			while let Some(index) = rest.find(&old) {
+				let after = &rest[index + old.len()..];
+				let ends = after
+					.chars()
+					.next()
+					.is_none_or(|c| !(c.is_alphanumeric() || " -_.".contains(c)));
+				output.push_str(&rest[..index]);
+				output.push_str(if ends { &new } else { &old });
+				rest = after;
+			}
+			output.push_str(rest);
+			content = output;
+		}
+		content
+	}
+
+	fn run_engine(&self, engine: &Engine) -> Result<Output> {
+		engine
+			.command(files::LATEX_BUILD, ".")
+			.current_dir(&self.build_dir)
+			.stdout(Stdio::piped())
+			.stderr(Stdio::piped())
+			.output()
+			.context(format!("Failed to run {}", engine.program()))
+	}
+
+	pub fn failure_log(&self) -> Option<PathBuf> {
+		let log = self.build_dir.join(files::FAIL_LOG);
+		if log.exists() {
+			Some(log)
+		} else {
+			None
+		}
+	}
+
+	pub fn clean_build_dir(&self) -> Result<()> {
+		if self.build_dir.exists() {
+			fs::remove_dir_all(&self.build_dir)?;
+		}
+		for parent in self.build_dir.ancestors().skip(1) {
+			if parent == Path::new(folders::BUILD) || !parent.exists() {
+				break;
+			}
+			if fs::read_dir(parent)?.next().is_some() {
+				break;
+			}
+			fs::remove_dir(parent)?;
+		}
+		Ok(())
+	}
+
 	pub fn view(&self, config: &Config, blocking: bool, build: bool) -> Result<()> {
 		if build && !self.pdf_path.exists() {
-			self.build(config, &config.latexmk)?;
+			self.build_pdf(config, None)?;
 		}
-		let view_with = config.view_with.as_ref().unwrap();
-		let mut cmd = Command::new(view_with.get(0).unwrap());
+		let view_with = config.view_with()?;
+		let mut cmd = Command::new(&view_with[0]);
 		cmd.args(view_with.iter().skip(1));
 		cmd.arg(&self.pdf_path);
+		let context = || format!("Failed to run {}", view_with[0]);
 		if blocking {
-			cmd.output()?;
+			cmd.output().with_context(context)?;
 		} else {
-			cmd.spawn()?;
+			cmd.spawn().with_context(context)?;
 		}
 		Ok(())
 	}
 
 	pub fn open(&self, config: &Config) -> Result<()> {
-		let open_with = config.open_with.as_ref().unwrap();
-		Command::new(open_with.get(0).unwrap())
+		let open_with = config.open_with()?;
+		Command::new(&open_with[0])
 			.args(open_with.iter().skip(1))
 			.arg(&self.path)
-			.status()?;
+			.status()
+			.with_context(|| format!("Failed to run {}", open_with[0]))?;
 		Ok(())
 	}
 
 	pub fn reveal(&self, config: &Config, build: bool) -> Result<()> {
 		if build && !self.pdf_path.exists() {
-			self.build(config, &config.latexmk)?;
+			self.build_pdf(config, None)?;
 		}
 
 		let (cmd, args) = match consts::OS {
@@ -332,28 +810,147 @@ impl Branch {
 	}
 }
 
+// Branches can share a folder (images have no {type} in the default layout), so the same move
+// planned for two of them is only done once. Different sources landing on one path are a clash
+pub fn merge_moves(groups: &mut [Vec<(PathBuf, PathBuf)>]) -> Result<()> {
+	let mut planned: HashSet<(PathBuf, PathBuf)> = HashSet::new();
+	let mut destinations: HashMap<PathBuf, PathBuf> = HashMap::new();
+	for moves in groups.iter_mut() {
+		moves.retain(|m| planned.insert(m.clone()));
+		for (from, to) in moves.iter() {
+			if let Some(other) = destinations.insert(to.clone(), from.clone()) {
+				return Err(Error::new(
+					Kind::Config,
+					format!(
+						"{} and {} would both move to {}",
+						other.display(),
+						from.display(),
+						to.display()
+					),
+				)
+				.hint("pick a layout that keeps their paths apart or rename one of them")
+				.into());
+			}
+		}
+	}
+	Ok(())
+}
+
+// Names the bibliography files get in the build folder. Files from different folders can share a
+// name, so later ones get a number added
+fn build_names(bibliography: &Bibliography) -> Result<(Vec<String>, Option<String>)> {
+	let mut names: Vec<String> = Vec::new();
+	for file in bibliography.files.iter().chain(&bibliography.csl) {
+		let (stem, extension) = match (
+			file.file_stem().and_then(|s| s.to_str()),
+			file.extension().and_then(|e| e.to_str()),
+		) {
+			(Some(stem), extension) if file.file_name().is_some() => (stem, extension),
+			_ => {
+				return Err(Error::new(
+					Kind::Config,
+					format!("{} is not a valid bibliography file", file.display()),
+				)
+				.hint("point bibliography and csl at files, relative to the project root")
+				.into())
+			}
+		};
+		let extension = extension.map(|e| format!(".{}", e)).unwrap_or_default();
+		let mut name = format!("{}{}", stem, extension);
+		let mut n = 1;
+		while names.contains(&name) {
+			n += 1;
+			name = format!("{}-{}{}", stem, n, extension);
+		}
+		names.push(name);
+	}
+	let csl = bibliography.csl.as_ref().and_then(|_| names.pop());
+	Ok((names, csl))
+}
+
+fn bibliography_tool(latex: &str) -> Option<&'static str> {
+	let lines = latex
+		.lines()
+		.map(|l| l.trim())
+		.filter(|l| !l.starts_with('%'));
+	for line in lines {
+		if line.starts_with("\\usepackage") && line.contains("{biblatex}") {
+			return Some(if line.contains("backend=bibtex") {
+				"bibtex"
+			} else {
+				"biber"
+			});
+		}
+		if line.starts_with("\\bibliography{") {
+			return Some("bibtex");
+		}
+	}
+	None
+}
+
+fn bibliography_warning(tool: &str, output: &Output) -> Option<Diagnostic> {
+	if output.status.success() {
+		return None;
+	}
+	let summary = [&output.stderr, &output.stdout]
+		.iter()
+		.find_map(|o| {
+			String::from_utf8_lossy(o)
+				.lines()
+				.rev()
+				.map(|l| l.trim().to_string())
+				.find(|l| !l.is_empty())
+		})
+		.unwrap_or_default();
+	Some(Diagnostic {
+		severity: Severity::Warning,
+		kind: diagnostic::Kind::Other(format!(
+			"{} exited with {}: {}",
+			tool, output.status, summary
+		)),
+		line: None,
+		context: None,
+		source_line: None,
+	})
+}
+
+fn aux_has_content(aux: &str) -> bool {
+	aux.lines()
+		.map(|l| l.trim())
+		.any(|l| !l.is_empty() && l != "\\relax" && !l.starts_with("\\gdef \\@abspage@last"))
+}
+
 impl Display for Branch {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-		write!(f, "{} ({})", self.name, self.class.name)
+		match &self.archived {
+			Some(term) => write!(f, "{} ({}, {})", self.name, self.class.name, term),
+			None => write!(f, "{} ({})", self.name, self.class.name),
+		}
 	}
 }
 
 #[cfg(test)]
 mod test {
+	use std::collections::HashMap;
 	use std::path::PathBuf;
+	use std::process::Command;
+	use std::str::FromStr;
 	use std::time::SystemTime;
 
 	use anyhow::Result;
-	use chrono::{Datelike, Local, Month};
+	use chrono::{Datelike, Local, Month, NaiveDate};
 	use num_traits::FromPrimitive;
 
 	use crate::branch::Branch;
-	use crate::conf::{Class, DocumentType, Format};
+	use crate::conf::{Bibliography, Class, DocumentType, Format};
+	use crate::diagnostic::Severity;
+	use crate::header::Metadata;
+	use crate::layout::Layout;
 	use crate::template::{BranchTemplate, RootTemplate};
 
 	#[test]
 	fn new() -> Result<()> {
-		let date_now = Local::now().date();
+		let date_now = Local::now().date_naive();
 		let systemtime_now = SystemTime::now();
 		let month = Month::from_u32(date_now.month()).unwrap().name();
 		assert_eq!(
@@ -363,8 +960,19 @@ mod test {
 				DocumentType::Worksheet,
 				Class {
 					name: String::from("AP Physics 2"),
+					renamed_from: Vec::new(),
 					teacher: String::from("Mr. Feynman"),
-					active: true
+					active: None,
+					terms: Vec::new(),
+					engine: None,
+					bibliography: None,
+					outputs: None,
+					reference_doc: None,
+					format: None,
+					branch_template: None,
+					root_template: None,
+					weights: HashMap::new(),
+					credits: None
 				},
 				Some(BranchTemplate {
 					path: PathBuf::from("./templates/branch/base.tex.hbs"),
@@ -376,7 +984,8 @@ mod test {
 					name: String::from("base")
 				},
 				date_now,
-				systemtime_now
+				systemtime_now,
+				&Layout::default()
 			)?,
 			Branch {
 				name: String::from("Working"),
@@ -384,15 +993,38 @@ mod test {
 				doc_type: DocumentType::Worksheet,
 				class: Class {
 					name: String::from("AP Physics 2"),
+					renamed_from: Vec::new(),
 					teacher: String::from("Mr. Feynman"),
-					active: true
+					active: None,
+					terms: Vec::new(),
+					engine: None,
+					bibliography: None,
+					outputs: None,
+					reference_doc: None,
+					format: None,
+					branch_template: None,
+					root_template: None,
+					weights: HashMap::new(),
+					credits: None
 				},
 				path: PathBuf::from(format!("docs/AP Physics 2/{}/Worksheet/Working.tex", month)),
 				pdf_path: PathBuf::from(format!(
 					"pdfs/AP Physics 2/{}/Worksheet/Working.pdf",
 					month
 				)),
+				html_path: PathBuf::from(format!(
+					"html/AP Physics 2/{}/Worksheet/Working.html",
+					month
+				)),
+				docx_path: PathBuf::from(format!(
+					"docx/AP Physics 2/{}/Worksheet/Working.docx",
+					month
+				)),
 				imgs_dir: PathBuf::from(format!("imgs/AP Physics 2/{}/Working", month)),
+				build_dir: PathBuf::from(format!(
+					".build/AP Physics 2/{}/Worksheet/Working",
+					month
+				)),
 				branch_template: Some(BranchTemplate {
 					path: PathBuf::from("./templates/branch/base.tex.hbs"),
 					name: String::from("base"),
@@ -403,7 +1035,10 @@ mod test {
 					name: String::from("base")
 				},
 				creation_time: date_now,
-				mod_time: systemtime_now
+				mod_time: systemtime_now,
+				bibliography: None,
+				metadata: Metadata::default(),
+				archived: None
 			}
 		);
 
@@ -414,8 +1049,19 @@ mod test {
 				DocumentType::Other,
 				Class {
 					name: String::from("Economics Honors"),
+					renamed_from: Vec::new(),
 					teacher: String::from("Mr. Buffet"),
-					active: true
+					active: None,
+					terms: Vec::new(),
+					engine: None,
+					bibliography: None,
+					outputs: None,
+					reference_doc: None,
+					format: None,
+					branch_template: None,
+					root_template: None,
+					weights: HashMap::new(),
+					credits: None
 				},
 				Some(BranchTemplate {
 					path: PathBuf::from("./templates/branch/base.tex.hbs"),
@@ -427,7 +1073,8 @@ mod test {
 					name: String::from("base")
 				},
 				date_now,
-				systemtime_now
+				systemtime_now,
+				&Layout::default()
 			)?,
 			Branch {
 				name: String::from("Hello World"),
@@ -435,8 +1082,19 @@ mod test {
 				doc_type: DocumentType::Other,
 				class: Class {
 					name: String::from("Economics Honors"),
+					renamed_from: Vec::new(),
 					teacher: String::from("Mr. Buffet"),
-					active: true
+					active: None,
+					terms: Vec::new(),
+					engine: None,
+					bibliography: None,
+					outputs: None,
+					reference_doc: None,
+					format: None,
+					branch_template: None,
+					root_template: None,
+					weights: HashMap::new(),
+					credits: None
 				},
 				path: PathBuf::from(format!(
 					"docs/Economics Honors/{}/Other/Hello World.md",
@@ -446,7 +1104,19 @@ mod test {
 					"pdfs/Economics Honors/{}/Other/Hello World.pdf",
 					month
 				)),
+				html_path: PathBuf::from(format!(
+					"html/Economics Honors/{}/Other/Hello World.html",
+					month
+				)),
+				docx_path: PathBuf::from(format!(
+					"docx/Economics Honors/{}/Other/Hello World.docx",
+					month
+				)),
 				imgs_dir: PathBuf::from(format!("imgs/Economics Honors/{}/Hello World", month)),
+				build_dir: PathBuf::from(format!(
+					".build/Economics Honors/{}/Other/Hello World",
+					month
+				)),
 				branch_template: Some(BranchTemplate {
 					path: PathBuf::from("./templates/branch/base.tex.hbs"),
 					name: String::from("base"),
@@ -457,10 +1127,186 @@ mod test {
 					name: String::from("base")
 				},
 				creation_time: date_now,
-				mod_time: systemtime_now
+				mod_time: systemtime_now,
+				bibliography: None,
+				metadata: Metadata::default(),
+				archived: None
 			}
 		);
 
 		Ok(())
 	}
+
+	#[test]
+	fn bibliography_tool() {
+		assert_eq!(
+			super::bibliography_tool("\\usepackage[style=apa]{biblatex}"),
+			Some("biber")
+		);
+		assert_eq!(
+			super::bibliography_tool("\\usepackage[backend=bibtex]{biblatex}"),
+			Some("bibtex")
+		);
+		assert_eq!(
+			super::bibliography_tool("\\bibliographystyle{plain}\n\\bibliography{refs}"),
+			Some("bibtex")
+		);
+		assert_eq!(
+			super::bibliography_tool("% \\usepackage{biblatex}\n\\begin{document}"),
+			None
+		);
+	}
+
+	#[test]
+	fn build_names() -> Result<()> {
+		let bibliography = Bibliography {
+			files: vec![
+				PathBuf::from("refs.bib"),
+				PathBuf::from("physics/refs.bib"),
+				PathBuf::from("physics/labs.bib"),
+			],
+			csl: Some(PathBuf::from("styles/apa.csl")),
+		};
+		assert_eq!(
+			super::build_names(&bibliography)?,
+			(
+				vec![
+					String::from("refs.bib"),
+					String::from("refs-2.bib"),
+					String::from("labs.bib")
+				],
+				Some(String::from("apa.csl"))
+			)
+		);
+		assert!(super::build_names(&Bibliography {
+			files: vec![PathBuf::from("..")],
+			csl: None
+		})
+		.is_err());
+		Ok(())
+	}
+
+	#[test]
+	#[cfg(unix)]
+	fn bibliography_warning() -> Result<()> {
+		let run = |script: &str| Command::new("sh").arg("-c").arg(script).output();
+		let warning = super::bibliography_warning(
+			"bibtex",
+			&run("echo 'Warning--empty year in smith'; echo '(There was 1 warning)'; exit 1")?,
+		)
+		.unwrap();
+		assert_eq!(warning.severity, Severity::Warning);
+		assert_eq!(
+			warning.kind.to_string(),
+			"bibtex exited with exit status: 1: (There was 1 warning)"
+		);
+		assert!(super::bibliography_warning("biber", &run("exit 0")?).is_none());
+		Ok(())
+	}
+
+	#[test]
+	fn aux_has_content() {
+		assert!(!super::aux_has_content(
+			"\\relax \n\\gdef \\@abspage@last{1}\n"
+		));
+		assert!(super::aux_has_content(
+			"\\relax \n\\newlabel{eq:1}{{1}{1}}\n"
+		));
+	}
+
+	#[test]
+	fn move_imgs_refs() -> Result<()> {
+		let branch = |name: &str, class: &str| {
+			Branch::new(
+				String::from(name),
+				Format::Markdown,
+				DocumentType::Lab,
+				Class {
+					name: String::from(class),
+					..Class::default()
+				},
+				None,
+				RootTemplate {
+					path: PathBuf::from("./templates/root/base.tex.hbs"),
+					name: String::from("base"),
+				},
+				NaiveDate::from_ymd_opt(2022, 10, 3).unwrap(),
+				SystemTime::now(),
+				&Layout::default(),
+			)
+		};
+		let content = [
+			"![Circuit](imgs/AP Physics 2/October/Circuits/circuit.png)",
+			"![Graph](../../../../imgs/AP Physics 2/October/Circuits/graph.png)",
+			"![Other](imgs/AP Physics 2/October/Circuits 2/circuit.png)",
+		]
+		.join("\n");
+		assert_eq!(
+			branch("Circuits", "AP Physics 2")?
+				.move_imgs_refs(&content, &branch("Resistors", "Physics C")?),
+			[
+				"![Circuit](imgs/Physics C/October/Resistors/circuit.png)",
+				"![Graph](../../../../imgs/Physics C/October/Resistors/graph.png)",
+				"![Other](imgs/AP Physics 2/October/Circuits 2/circuit.png)",
+			]
+			.join("\n")
+		);
+		Ok(())
+	}
+
+	#[test]
+	fn merge_moves() -> Result<()> {
+		let branch = |doc_type, class: &str, layout: &Layout| {
+			Branch::new(
+				String::from("Circuits"),
+				Format::Markdown,
+				doc_type,
+				Class {
+					name: String::from(class),
+					..Class::default()
+				},
+				None,
+				RootTemplate::from_filename("base.tex.hbs"),
+				NaiveDate::from_ymd_opt(2022, 10, 3).unwrap(),
+				SystemTime::now(),
+				layout,
+			)
+		};
+		let moves = |class: &str, layout: &Layout| -> Result<Vec<Vec<(PathBuf, PathBuf)>>> {
+			[DocumentType::Lab, DocumentType::Note]
+				.into_iter()
+				.map(|doc_type| {
+					let old = branch(doc_type, "AP Physics 2", &Layout::default())?;
+					let new = branch(doc_type, class, layout)?;
+					Ok(vec![(old.path, new.path), (old.imgs_dir, new.imgs_dir)])
+				})
+				.collect()
+		};
+
+		// Both branches use imgs/AP Physics 2/October/Circuits, which only needs moving once
+		let mut shared = moves("Physics C", &Layout::default())?;
+		super::merge_moves(&mut shared)?;
+		assert_eq!(shared[0].len(), 2);
+		assert_eq!(
+			shared[1],
+			vec![(
+				PathBuf::from("docs/AP Physics 2/October/Note/Circuits.md"),
+				PathBuf::from("docs/Physics C/October/Note/Circuits.md")
+			)]
+		);
+
+		// class rename plans its moves as one list
+		let mut renamed = vec![moves("Physics C", &Layout::default())?.concat()];
+		super::merge_moves(&mut renamed)?;
+		assert_eq!(renamed[0].len(), 3);
+
+		// Unless the new layout splits it up
+		let mut split = moves("AP Physics 2", &Layout::from_str("{class}/{type}/{name}")?)?;
+		super::merge_moves(&mut split)?;
+		assert_eq!(split.concat().len(), 4);
+
+		let mut clash = moves("AP Physics 2", &Layout::from_str("{class}/{name}")?)?;
+		assert!(super::merge_moves(&mut clash).is_err());
+		Ok(())
+	}
 }
@@ -0,0 +1,270 @@
+use std::io::BufRead;
+
+use anyhow::Result;
+use chrono::{Duration, Local, NaiveDate, NaiveDateTime, Utc};
+use ical::generator::{Emitter, IcalCalendar, IcalEvent, Property};
+use ical::parser::ical::component::IcalTodo;
+use ical::IcalParser;
+use strum_macros::{Display, EnumString, EnumVariantNames};
+
+use crate::branch::Branch;
+use crate::conf::Class;
+use crate::header::Status;
+
+#[derive(PartialEq, Debug, Display, EnumVariantNames, EnumString, Clone, Copy)]
+#[strum(serialize_all = "lowercase")]
+pub enum Kind {
+	Todo,
+	Event,
+}
+
+#[derive(PartialEq, Debug)]
+pub struct Assignment {
+	pub name: String,
+	pub due: NaiveDate,
+	pub courses: Vec<String>,
+}
+
+pub fn export(branches: &[&Branch], kind: Kind) -> String {
+	let stamp = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+	let mut calendar = IcalCalendar::new();
+	calendar.properties = vec![
+		property("VERSION", "2.0", None),
+		property("PRODID", "-//optic//optic//EN", None),
+		property("CALSCALE", "GREGORIAN", None),
+	];
+
+	for branch in branches {
+		let due = match branch.metadata.due {
+			Some(due) => due,
+			None => continue,
+		};
+		let mut properties = vec![
+			property(
+				"UID",
+				&escape(&format!("{}@optic", branch.path.display())),
+				None,
+			),
+			property("DTSTAMP", &stamp, None),
+			property("SUMMARY", &escape(&branch.name), None),
+			property(
+				"DESCRIPTION",
+				&escape(&format!(
+					"Class: {}\nTeacher: {}\nType: {}\nPath: {}",
+					branch.class.name,
+					branch.class.teacher,
+					branch.doc_type,
+					branch.path.display()
+				)),
+				None,
+			),
+			property(
+				"CATEGORIES",
+				&format!(
+					"{},{}",
+					escape(&branch.class.name),
+					escape(&branch.doc_type.to_string())
+				),
+				None,
+			),
+		];
+		match kind {
+			Kind::Todo => {
+				properties.push(property("DUE", &date(due), Some("DATE")));
+				if let Some(status) = branch.metadata.status {
+					properties.push(property(
+						"STATUS",
+						match status {
+							Status::Todo => "NEEDS-ACTION",
+							Status::InProgress => "IN-PROCESS",
+							Status::Submitted | Status::Graded => "COMPLETED",
+						},
+						None,
+					));
+				}
+				let mut todo = IcalTodo::new();
+				todo.properties = properties;
+				calendar.todos.push(todo);
+			}
+			Kind::Event => {
+				properties.push(property("DTSTART", &date(due), Some("DATE")));
+				properties.push(property(
+					"DTEND",
+					&date(due + Duration::days(1)),
+					Some("DATE"),
+				));
+				let mut event = IcalEvent::new();
+				event.properties = properties;
+				calendar.events.push(event);
+			}
+		}
+	}
+	calendar.generate()
+}
+
+pub fn import<B: BufRead>(reader: B) -> Result<Vec<Assignment>> {
+	let mut assignments = Vec::new();
+	for calendar in IcalParser::new(reader) {
+		let calendar = calendar?;
+		let components = calendar
+			.events
+			.iter()
+			.map(|e| (&e.properties, "DTSTART"))
+			.chain(calendar.todos.iter().map(|t| (&t.properties, "DUE")));
+		for (properties, due_property) in components {
+			let summary = match get(properties, "SUMMARY").and_then(value) {
+				Some(summary) => summary,
+				None => continue,
+			};
+			let due = match get(properties, due_property).and_then(parse_date) {
+				Some(due) => due,
+				None => continue,
+			};
+
+			let mut courses = Vec::new();
+			let name = match summary
+				.trim()
+				.strip_suffix(']')
+				.and_then(|s| s.rsplit_once(" ["))
+			{
+				Some((name, course)) => {
+					courses.push(course.to_string());
+					name.to_string()
+				}
+				None => summary.trim().to_string(),
+			};
+			if let Some(categories) = get(properties, "CATEGORIES").and_then(value) {
+				courses.extend(categories.split(',').map(|c| c.trim().to_string()));
+			}
+			if let Some(location) = get(properties, "LOCATION").and_then(value) {
+				courses.push(location);
+			}
+
+			assignments.push(Assignment {
+				name: name.replace(['/', '\\'], "-"),
+				due,
+				courses,
+			});
+		}
+	}
+	Ok(assignments)
+}
+
+impl Assignment {
+	pub fn class<'a>(&self, classes: &'a [Class]) -> Option<&'a Class> {
+		classes.iter().find(|class| {
+			self.courses.iter().any(|course| {
+				let course = course.to_lowercase();
+				let name = class.name.to_lowercase();
+				!course.is_empty() && (course.contains(&name) || name.contains(&course))
+			})
+		})
+	}
+}
+
+fn property(name: &str, value: &str, value_type: Option<&str>) -> Property {
+	Property {
+		name: name.to_string(),
+		params: value_type.map(|t| vec![(String::from("VALUE"), vec![t.to_string()])]),
+		value: Some(value.to_string()),
+	}
+}
+
+fn get<'a>(properties: &'a [Property], name: &str) -> Option<&'a Property> {
+	properties.iter().find(|p| p.name == name)
+}
+
+fn date(date: NaiveDate) -> String { date.format("%Y%m%d").to_string() }
+
+fn parse_date(property: &Property) -> Option<NaiveDate> {
+	let value = property.value.as_ref()?;
+	if let Ok(time) = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%SZ") {
+		return Some(time.and_utc().with_timezone(&Local).date_naive());
+	}
+	NaiveDate::parse_from_str(value.get(..8)?, "%Y%m%d").ok()
+}
+
+fn value(property: &Property) -> Option<String> { property.value.as_deref().map(unescape) }
+
+fn escape(text: &str) -> String {
+	text.replace('\\', "\\\\")
+		.replace(';', "\\;")
+		.replace(',', "\\,")
+		.replace('\n', "\\n")
+}
+
+fn unescape(text: &str) -> String {
+	let mut output = String::new();
+	let mut chars = text.chars();
+	while let Some(c) = chars.next() {
+		if c != '\\' {
+			output.push(c);
+			continue;
+		}
+		match chars.next() {
+			Some('n' | 'N') => output.push('\n'),
+			Some(c) => output.push(c),
+			None => output.push('\\'),
+		}
+	}
+	output
+}
+
+#[cfg(test)]
+mod test {
+	use chrono::NaiveDate;
+
+	use super::{escape, import, unescape, Assignment};
+
+	#[test]
+	fn parse() -> anyhow::Result<()> {
+		let ics = [
+			"BEGIN:VCALENDAR",
+			"VERSION:2.0",
+			"BEGIN:VEVENT",
+			"UID:event-assignment-1",
+			"DTSTART;VALUE=DATE:20221007",
+			"DTEND;VALUE=DATE:20221008",
+			"SUMMARY:Lab 3: Circuits [AP Physics 2]",
+			"END:VEVENT",
+			"BEGIN:VTODO",
+			"UID:todo-1",
+			"DUE;VALUE=DATE:20221010",
+			"SUMMARY:Reading\\, chapter 4",
+			"CATEGORIES:English",
+			"END:VTODO",
+			"BEGIN:VEVENT",
+			"UID:no-date",
+			"SUMMARY:Office hours",
+			"END:VEVENT",
+			"END:VCALENDAR",
+		]
+		.join("\r\n");
+		assert_eq!(
+			import(ics.as_bytes())?,
+			vec![
+				Assignment {
+					name: String::from("Lab 3: Circuits"),
+					due: NaiveDate::from_ymd_opt(2022, 10, 7).unwrap(),
+					courses: vec![String::from("AP Physics 2")],
+				},
+				Assignment {
+					name: String::from("Reading, chapter 4"),
+					due: NaiveDate::from_ymd_opt(2022, 10, 10).unwrap(),
+					courses: vec![String::from("English")],
+				},
+			]
+		);
+		Ok(())
+	}
+
+	#[test]
+	fn text() {
+		let text = "Class: AP Physics 2\nPath: docs/a;b,c\\d";
+		assert_eq!(
+			escape(text),
+			"Class: AP Physics 2\\nPath: docs/a\\;b\\,c\\\\d"
+		);
+		assert_eq!(unescape(&escape(text)), text);
+	}
+}
@@ -0,0 +1,263 @@
+use std::ops::Range;
+
+use anyhow::{Context, Result};
+use toml::value::{Table, Value};
+
+use crate::locations::files;
+
+// optic.toml is edited line by line so comments and formatting survive
+
+pub fn add(content: &str, name: &str, teacher: &str) -> Result<String> {
+	let mut lines = lines(content);
+	let block = format!(
+		"[[classes]]\nname = {}\nteacher = {}\n",
+		quote(name),
+		quote(teacher)
+	);
+	match blocks(&lines).last() {
+		Some(last) => {
+			let mut insert = last.end;
+			while insert > last.start && lines[insert - 1].trim().is_empty() {
+				insert -= 1;
+			}
+			if !lines[insert - 1].ends_with('\n') {
+				lines[insert - 1].push('\n');
+			}
+			lines.insert(insert, format!("\n{}", block));
+		}
+		None => {
+			if let Some(last) = lines.last_mut() {
+				if !last.ends_with('\n') {
+					last.push('\n');
+				}
+				lines.push(String::from("\n"));
+			}
+			lines.push(block);
+		}
+	}
+	Ok(lines.concat())
+}
+
+// The old name is kept in renamed_from so branches can still be found by it
+pub fn rename(content: &str, old: &str, new: &str) -> Result<String> {
+	let mut lines = lines(content);
+	let block = find(&lines, old)?;
+	let index = key_line(&lines, &block, "name").context("Class has no name")?;
+	lines[index] = format!("{}name = {}\n", indent(&lines[index]), quote(new));
+
+	let line = |names: Vec<String>| {
+		format!(
+			"renamed_from = {}\n",
+			Value::Array(names.into_iter().map(Value::String).collect())
+		)
+	};
+	match key_line(&lines, &block, "renamed_from") {
+		Some(i) => {
+			let mut names: Vec<String> = value(&lines[i])
+				.and_then(|v| v.try_into().ok())
+				.context("renamed_from is not a list of names")?;
+			names.retain(|n| n != new);
+			if !names.iter().any(|n| n == old) {
+				names.push(old.to_string());
+			}
+			lines[i] = format!("{}{}", indent(&lines[i]), line(names));
+		}
+		None => {
+			let new_line = format!("{}{}", indent(&lines[index]), line(vec![old.to_string()]));
+			if !lines[index].ends_with('\n') {
+				lines[index].push('\n');
+			}
+			lines.insert(index + 1, new_line);
+		}
+	}
+	Ok(lines.concat())
+}
+
+pub fn set_active(content: &str, name: &str, active: Option<bool>) -> Result<String> {
+	let mut lines = lines(content);
+	let block = find(&lines, name)?;
+	match (key_line(&lines, &block, "active"), active) {
+		(Some(index), Some(active)) => {
+			lines[index] = format!("{}active = {}\n", indent(&lines[index]), active);
+		}
+		(Some(index), None) => {
+			lines.remove(index);
+		}
+		(None, Some(active)) => {
+			let after = key_line(&lines, &block, "teacher")
+				.or_else(|| key_line(&lines, &block, "name"))
+				.unwrap_or(block.start);
+			let line = format!("{}active = {}\n", indent(&lines[after]), active);
+			if !lines[after].ends_with('\n') {
+				lines[after].push('\n');
+			}
+			lines.insert(after + 1, line);
+		}
+		(None, None) => {}
+	}
+	Ok(lines.concat())
+}
+
+pub fn remove(content: &str, name: &str) -> Result<String> {
+	let mut lines = lines(content);
+	let mut block = find(&lines, name)?;
+	// Comments right above the entry go with it
+	while block.start > 0 && lines[block.start - 1].trim_start().starts_with('#') {
+		block.start -= 1;
+	}
+	lines.drain(block);
+	Ok(lines.concat())
+}
+
+fn lines(content: &str) -> Vec<String> { content.split_inclusive('\n').map(String::from).collect() }
+
+fn quote(value: &str) -> String { Value::String(value.to_string()).to_string() }
+
+fn indent(line: &str) -> &str { &line[..line.len() - line.trim_start().len()] }
+
+fn header(line: &str) -> Option<&str> {
+	let line = line.trim();
+	if !line.starts_with('[') {
+		return None;
+	}
+	let end = line.find(']')?;
+	Some(line[..end].trim_start_matches('[').trim())
+}
+
+// Each [[classes]] entry runs until the next table that isn't one of its sub-tables. Comments
+// right before the next table are left with it
+fn blocks(lines: &[String]) -> Vec<Range<usize>> {
+	let mut blocks = Vec::new();
+	let mut start = None;
+	for (i, line) in lines.iter().enumerate() {
+		let table = match header(line) {
+			Some(table) if !table.starts_with("classes.") => table,
+			_ => continue,
+		};
+		if let Some(start) = start.take() {
+			blocks.push(start..block_end(lines, start, i));
+		}
+		if table == "classes" && line.trim_start().starts_with("[[") {
+			start = Some(i);
+		}
+	}
+	if let Some(start) = start {
+		blocks.push(start..lines.len());
+	}
+	blocks
+}
+
+fn block_end(lines: &[String], start: usize, mut end: usize) -> usize {
+	while end > start + 1 && lines[end - 1].trim_start().starts_with('#') {
+		end -= 1;
+	}
+	end
+}
+
+fn find(lines: &[String], name: &str) -> Result<Range<usize>> {
+	blocks(lines)
+		.into_iter()
+		.find(|block| {
+			key_line(lines, block, "name")
+				.and_then(|i| value(&lines[i]))
+				.is_some_and(|v| v.as_str() == Some(name))
+		})
+		.with_context(|| format!("No [[classes]] entry named {} in {}", name, files::CONFIG))
+}
+
+// Only looks at the class's own keys, not the ones in its sub-tables
+fn key_line(lines: &[String], block: &Range<usize>, key: &str) -> Option<usize> {
+	(block.start + 1..block.end)
+		.take_while(|i| header(&lines[*i]).is_none())
+		.find(|i| {
+			toml::from_str::<Table>(&lines[*i])
+				.ok()
+				.is_some_and(|t| t.contains_key(key))
+		})
+}
+
+fn value(line: &str) -> Option<Value> {
+	toml::from_str::<Table>(line)
+		.ok()?
+		.into_iter()
+		.next()
+		.map(|(_, v)| v)
+}
+
+#[cfg(test)]
+mod test {
+	use super::{add, remove, rename, set_active};
+	use crate::conf::Config;
+
+	fn config() -> String {
+		[
+			"author = \"Matt Gleich\"",
+			"",
+			"# Physics",
+			"[[classes]]",
+			"name = \"AP Physics 2\"",
+			"teacher = \"Mr. Feynman\"",
+			"",
+			"[classes.weights]",
+			"Lab = 25",
+			"",
+			"# Keep this one",
+			"[[classes]]",
+			"name = \"English\"",
+			"teacher = \"Ms. Woolf\" # Room 204",
+			"",
+			"[bibliography]",
+			"files = [\"refs.bib\"]",
+			"",
+		]
+		.join("\n")
+	}
+
+	#[test]
+	fn edit() -> anyhow::Result<()> {
+		let content = config();
+
+		let added = add(&content, "Chemistry", "Mrs. Curie")?;
+		assert!(added.contains(
+			&[
+				"teacher = \"Ms. Woolf\" # Room 204",
+				"",
+				"[[classes]]",
+				"name = \"Chemistry\"",
+				"teacher = \"Mrs. Curie\"",
+				"",
+				"[bibliography]",
+			]
+			.join("\n")
+		));
+		let parsed = toml::from_str::<Config>(&added)?;
+		assert_eq!(parsed.classes.len(), 3);
+		assert_eq!(parsed.bibliography.unwrap().files.len(), 1);
+
+		let renamed = rename(&content, "AP Physics 2", "Physics C")?;
+		assert_eq!(
+			renamed,
+			content.replace(
+				"name = \"AP Physics 2\"",
+				"name = \"Physics C\"\nrenamed_from = [\"AP Physics 2\"]"
+			)
+		);
+		assert_eq!(
+			rename(&renamed, "Physics C", "AP Physics 2")?,
+			content.replace(
+				"name = \"AP Physics 2\"",
+				"name = \"AP Physics 2\"\nrenamed_from = [\"Physics C\"]"
+			)
+		);
+
+		let inactive = set_active(&content, "English", Some(false))?;
+		assert!(inactive.contains("teacher = \"Ms. Woolf\" # Room 204\nactive = false\n"));
+		assert_eq!(set_active(&inactive, "English", None)?, content);
+
+		let removed = remove(&content, "AP Physics 2")?;
+		assert!(removed.starts_with("author = \"Matt Gleich\"\n\n# Keep this one\n[[classes]]"));
+		assert_eq!(toml::from_str::<Config>(&removed)?.classes.len(), 1);
+		assert!(remove(&content, "Biology").is_err());
+		Ok(())
+	}
+}
@@ -1,5 +1,9 @@
-use clap::{value_parser, Arg, Command};
+use clap::{value_parser, Arg, ArgGroup, Command};
 use clap_complete::Shell;
+use strum::VariantNames;
+
+use crate::conf::{DocumentType, Target};
+use crate::{calendar, selector, starter};
 
 pub fn setup() -> Command<'static> {
 	Command::new("optic")
@@ -7,31 +11,193 @@ pub fn setup() -> Command<'static> {
 		.author("Matt Gleich <email@mattglei.ch>")
 		.about("Schoolwork as code")
 		.arg_required_else_help(true)
-		.subcommand(Command::new("new").about("Create a new branch"))
 		.subcommand(
-			Command::new("build").about("Build a branch").arg(
-				Arg::new("latexmk")
-					.long("latexmk")
-					.help("Use latexmk instead of pdflatex to build the PDF")
-					.takes_value(false),
-			),
+			Command::new("init")
+				.about("Create a new optic project")
+				.arg(
+					Arg::new("dir")
+						.help("Folder to create the project in")
+						.default_value("."),
+				)
+				.arg(
+					Arg::new("author")
+						.long("author")
+						.help("Name used in document titles")
+						.takes_value(true),
+				)
+				.arg(
+					Arg::new("format")
+						.long("format")
+						.help("Default format of new branches")
+						.takes_value(true)
+						.possible_values(starter::FORMATS),
+				)
+				.arg(
+					Arg::new("class")
+						.long("class")
+						.help("Class to add as NAME:TEACHER (can be repeated)")
+						.takes_value(true)
+						.multiple_occurrences(true),
+				)
+				.arg(
+					Arg::new("git")
+						.long("git")
+						.help("Initialize a git repository")
+						.conflicts_with("no-git"),
+				)
+				.arg(
+					Arg::new("no-git")
+						.long("no-git")
+						.help("Don't initialize a git repository"),
+				)
+				.arg(
+					Arg::new("yes")
+						.short('y')
+						.long("yes")
+						.help("Use defaults instead of asking for anything not given as a flag"),
+				),
+		)
+		.subcommand(
+			Command::new("new")
+				.about("Create a new branch")
+				.arg(
+					Arg::new("name")
+						.long("name")
+						.help("Name of the branch")
+						.takes_value(true),
+				)
+				.arg(
+					Arg::new("format")
+						.long("format")
+						.help("Format of the branch (e.g. LaTeX or Markdown)")
+						.takes_value(true),
+				)
+				.arg(
+					Arg::new("type")
+						.long("type")
+						.help("Document type of the branch (e.g. Worksheet)")
+						.takes_value(true),
+				)
+				.arg(
+					Arg::new("class")
+						.long("class")
+						.help("Name of the class the branch belongs to")
+						.takes_value(true),
+				)
+				.arg(
+					Arg::new("branch-template")
+						.long("branch-template")
+						.help("Name of the branch template to use")
+						.takes_value(true),
+				)
+				.arg(
+					Arg::new("root-template")
+						.long("root-template")
+						.help("Name of the root template to use")
+						.takes_value(true),
+				)
+				.arg(
+					Arg::new("due")
+						.long("due")
+						.help("Due date of the branch (e.g. friday, +3d, or 2022-10-07)")
+						.takes_value(true),
+				),
 		)
 		.subcommand(
-			Command::new("watch")
-				.about("View a branch and build it on change")
+			selector::args(Command::new("build").about("Build a branch"))
+				.arg(
+					Arg::new("engine")
+						.long("engine")
+						.help(
+							"Engine to build the PDF with (pdflatex, xelatex, lualatex, latexmk, \
+							 latexmk:<backend>, tectonic, or a command with {input} and {outdir})",
+						)
+						.takes_value(true),
+				)
 				.arg(
-					Arg::new("latexmk")
-						.long("latexmk")
-						.help("Use latexmk instead of pdflatex to build the PDF")
-						.takes_value(false),
+					Arg::new("to")
+						.long("to")
+						.help("Only build this output target (defaults to every configured target)")
+						.takes_value(true)
+						.possible_values(Target::VARIANTS),
+				)
+				.arg(
+					Arg::new("all")
+						.long("all")
+						.help("Build every branch that matches the filters")
+						.takes_value(false)
+						.conflicts_with_all(&["branch", "pick"]),
+				)
+				.arg(
+					Arg::new("jobs")
+						.long("jobs")
+						.short('j')
+						.help("Number of branches to build at once when using --all")
+						.takes_value(true)
+						.value_parser(value_parser!(usize))
+						.requires("all"),
 				),
 		)
-		.subcommand(Command::new("open").about("Open a branch in an editor"))
-		.subcommand(Command::new("reveal").about("Open a branch PDF in finder"))
+		.subcommand(
+			selector::args(Command::new("watch").about("View a branch and build it on change"))
+				.arg(
+					Arg::new("engine")
+						.long("engine")
+						.help(
+							"Engine to build the PDF with (pdflatex, xelatex, lualatex, latexmk, \
+							 latexmk:<backend>, tectonic, or a command with {input} and {outdir})",
+						)
+						.takes_value(true),
+				),
+		)
+		.subcommand(selector::args(
+			Command::new("open").about("Open a branch in an editor"),
+		))
+		.subcommand(selector::args(
+			Command::new("reveal").about("Open a branch PDF in finder"),
+		))
 		.subcommand(
 			Command::new("check").about("Check to see if any branches don't have up-to-date PDFs"),
 		)
-		.subcommand(Command::new("trash").about("Move branch to trash can"))
+		.subcommand(selector::args(
+			Command::new("trash").about("Move branch to trash can"),
+		))
+		.subcommand(
+			selector::args(
+				Command::new("mv")
+					.about("Rename a branch or change its class or type")
+					.long_about(
+						"Rename a branch or change its class or type. The branch, its outputs and \
+						 its images are moved together (with git mv when they're tracked) and \
+						 references to its images folder are updated.",
+					),
+			)
+			.arg(
+				Arg::new("name")
+					.long("name")
+					.help("New name of the branch")
+					.takes_value(true),
+			)
+			.arg(
+				Arg::new("to-class")
+					.long("to-class")
+					.help("Class to move the branch to")
+					.takes_value(true),
+			)
+			.arg(
+				Arg::new("to-type")
+					.long("to-type")
+					.help("Document type to change the branch to")
+					.possible_values(DocumentType::VARIANTS)
+					.takes_value(true),
+			)
+			.group(
+				ArgGroup::new("changes")
+					.args(&["name", "to-class", "to-type"])
+					.multiple(true)
+					.required(true),
+			),
+		)
 		.subcommand(
 			Command::new("completion")
 				.about("Generate shell completion for optic")
@@ -41,6 +207,188 @@ pub fn setup() -> Command<'static> {
 						.value_parser(value_parser!(Shell)),
 				),
 		)
+		.subcommand(Command::new("failures").about("List branches whose last build failed"))
 		.subcommand(Command::new("commit").about("Commit uncommitted branches"))
 		.subcommand(Command::new("search").about("Search for a branch"))
+		.subcommand(Command::new("agenda").about("List open branches by due date"))
+		.subcommand(
+			Command::new("grades")
+				.about("Report averages per class and an overall GPA")
+				.arg(
+					Arg::new("format")
+						.long("format")
+						.help("Format to print the report in")
+						.takes_value(true)
+						.possible_values(["table", "json", "csv"])
+						.default_value("table"),
+				),
+		)
+		.subcommand(
+			Command::new("calendar")
+				.about("Export or import assignment deadlines as iCalendar files")
+				.arg_required_else_help(true)
+				.subcommand(
+					Command::new("export")
+						.about("Write branches with due dates to an .ics file")
+						.arg(
+							Arg::new("output")
+								.short('o')
+								.long("output")
+								.help("Path of the .ics file to write")
+								.takes_value(true)
+								.default_value("optic.ics"),
+						)
+						.arg(
+							Arg::new("as")
+								.long("as")
+								.help("Write each branch as a todo or an all-day event")
+								.takes_value(true)
+								.possible_values(calendar::Kind::VARIANTS)
+								.default_value("todo"),
+						),
+				)
+				.subcommand(
+					Command::new("import")
+						.about("Create branches for the assignments in an .ics file")
+						.arg(
+							Arg::new("file")
+								.help("Calendar exported from a learning management system")
+								.required(true),
+						)
+						.arg(
+							Arg::new("class")
+								.long("class")
+								.help(
+									"Class to create every branch in instead of matching by course",
+								)
+								.takes_value(true),
+						)
+						.arg(
+							Arg::new("type")
+								.long("type")
+								.help("Document type of the created branches")
+								.takes_value(true)
+								.possible_values(DocumentType::VARIANTS)
+								.default_value("Worksheet"),
+						)
+						.arg(
+							Arg::new("past")
+								.long("past")
+								.help("Also create branches for assignments that are already due"),
+						),
+				),
+		)
+		.subcommand(
+			Command::new("archive")
+				.about("Move a term's branches and outputs into the archive folder")
+				.arg(
+					Arg::new("term")
+						.help("Name of the term in optic.toml")
+						.required(true),
+				)
+				.arg(
+					Arg::new("tag")
+						.long("tag")
+						.help("Tag the current commit instead of moving any files"),
+				),
+		)
+		.subcommand(
+			Command::new("relayout")
+				.about("Move branches and their outputs to match the layout in optic.toml")
+				.arg(
+					Arg::new("from")
+						.long("from")
+						.help(
+							"Layout the outputs and images are currently in (defaults to \
+							 class/month/type/name)",
+						)
+						.takes_value(true),
+				)
+				.arg(
+					Arg::new("dry-run")
+						.long("dry-run")
+						.help("Print the moves without making them"),
+				),
+		)
+		.subcommand(
+			Command::new("migrate-headers")
+				.about("Rewrite old delimiter headers as YAML or TOML front matter"),
+		)
+		.subcommand(
+			Command::new("config")
+				.about("Inspect the configuration")
+				.long_about(
+					"Inspect the configuration. optic.toml is found in the current folder or the \
+					 closest parent folder. author, open_with, view_with and engine can also be \
+					 set in ~/.config/optic/config.toml ($XDG_CONFIG_HOME/optic/config.toml), \
+					 which optic.toml overrides.",
+				)
+				.arg_required_else_help(true)
+				.subcommand(
+					Command::new("show")
+						.about("Print the effective configuration and where each value comes from"),
+				)
+				.subcommand(Command::new("check").about(
+					"Validate optic.toml against the templates and docs folders and report every \
+					 problem",
+				)),
+		)
+		.subcommand(
+			Command::new("class")
+				.about("Add, list, rename, activate, deactivate or remove classes")
+				.arg_required_else_help(true)
+				.subcommand(
+					Command::new("add")
+						.about("Add a class to optic.toml")
+						.arg(Arg::new("name").help("Name of the class"))
+						.arg(
+							Arg::new("teacher")
+								.long("teacher")
+								.short('t')
+								.help("Teacher of the class")
+								.takes_value(true),
+						),
+				)
+				.subcommand(
+					Command::new("list")
+						.about("List classes with their branch counts and last activity"),
+				)
+				.subcommand(
+					Command::new("rename")
+						.about("Rename a class and move its branches, outputs and images to match")
+						.arg(
+							Arg::new("old")
+								.help("Current name of the class")
+								.required(true),
+						)
+						.arg(Arg::new("new").help("New name of the class").required(true)),
+				)
+				.subcommand(
+					Command::new("deactivate")
+						.about("Stop offering a class when creating branches")
+						.arg(Arg::new("name").help("Name of the class").required(true)),
+				)
+				.subcommand(
+					Command::new("activate")
+						.about("Offer a class again when creating branches")
+						.arg(Arg::new("name").help("Name of the class").required(true)),
+				)
+				.subcommand(
+					Command::new("remove")
+						.about("Remove a class from optic.toml")
+						.arg(Arg::new("name").help("Name of the class").required(true))
+						.arg(
+							Arg::new("archive")
+								.long("archive")
+								.help("Archive the class's branches into its last term first"),
+						)
+						.arg(
+							Arg::new("term")
+								.long("term")
+								.help("Archive into this term instead of the class's last one")
+								.takes_value(true)
+								.requires("archive"),
+						),
+				),
+		)
 }
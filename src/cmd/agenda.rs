@@ -0,0 +1,77 @@
+use anyhow::{Context, Result};
+use chrono::{Duration, Local, NaiveDate};
+use chrono_humanize::HumanTime;
+use colorful::Colorful;
+
+use crate::branch::Branch;
+use crate::conf::Config;
+
+pub fn run() -> Result<()> {
+	let config = Config::read().context("Failed to read from configuration file")?;
+	let mut branches: Vec<(NaiveDate, Branch)> = Branch::get_all(&config)
+		.context("Failed to get all branches")?
+		.into_iter()
+		.filter(|b| b.metadata.status.is_none_or(|s| s.is_open()))
+		.filter_map(|b| b.metadata.due.map(|due| (due, b)))
+		.collect();
+	branches.sort_by_key(|(due, _)| *due);
+
+	if branches.is_empty() {
+		println!("0 open branches with due dates");
+		return Ok(());
+	}
+
+	let today = Local::now().date_naive();
+	let groups = [
+		(
+			" OVERDUE ".bg_red().black().to_string(),
+			None,
+			Some(today - Duration::days(1)),
+		),
+		(
+			" TODAY ".bg_yellow().black().to_string(),
+			Some(today),
+			Some(today),
+		),
+		(
+			" THIS WEEK ".bg_blue().black().to_string(),
+			Some(today + Duration::days(1)),
+			Some(today + Duration::days(7)),
+		),
+		(
+			" LATER ".bg_green().black().to_string(),
+			Some(today + Duration::days(8)),
+			None,
+		),
+	];
+	for (label, start, end) in groups {
+		let group: Vec<&(NaiveDate, Branch)> = branches
+			.iter()
+			.filter(|(due, _)| start.is_none_or(|s| *due >= s) && end.is_none_or(|e| *due <= e))
+			.collect();
+		if group.is_empty() {
+			continue;
+		}
+
+		println!("\n  {}", label);
+		for (due, branch) in group {
+			println!(
+				"   {} {} ({}, {}){}",
+				due.format("%a %b %e").to_string().bold(),
+				branch.name,
+				branch.class.name,
+				branch.doc_type,
+				branch
+					.metadata
+					.status
+					.map(|s| format!(" [{}]", s))
+					.unwrap_or_default()
+					.dim()
+			);
+			if *due != today {
+				println!("      {}", HumanTime::from(*due - today).to_string().dim());
+			}
+		}
+	}
+	Ok(())
+}
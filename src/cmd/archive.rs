@@ -0,0 +1,138 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use clap::ArgMatches;
+use task_log::task;
+
+use crate::branch::{self, Branch};
+use crate::cmd::relayout;
+use crate::conf::{Config, Term};
+use crate::error::{Error, Kind};
+use crate::locations::files;
+
+pub fn run(args: &ArgMatches) -> Result<()> {
+	let config = Config::read().context("Failed to read from configuration file")?;
+	let term = config
+		.term(args.value_of("term").unwrap())
+		.context("Failed to find term")?;
+	check_term(term)?;
+	let branches = Branch::get_all_with_archive(&config).context("Failed to get all branches")?;
+	let branches: Vec<&Branch> = branches.iter().filter(|b| b.in_term(term)).collect();
+
+	if args.is_present("tag") {
+		return task(format!("Tagging {}", term.name), || {
+			tag(term, branches.len())
+		})
+		.context("Failed to tag term");
+	}
+
+	let branches: Vec<&Branch> = branches
+		.into_iter()
+		.filter(|b| b.archived.is_none())
+		.collect();
+	apply(&plan(&branches, &term.name)?, || Ok(()))?;
+	println!(
+		"{} {} archived into {}",
+		branches.len(),
+		if branches.len() == 1 {
+			"branch"
+		} else {
+			"branches"
+		},
+		Term::folder(&term.name).display()
+	);
+	Ok(())
+}
+
+pub fn check_term(term: &Term) -> Result<()> {
+	match Term::name_problem(&term.name) {
+		Some(problem) => {
+			Err(Error::new(
+				Kind::Config,
+				format!("The term name {} {}", term.name, problem),
+			)
+			.hint(format!("rename it in {}", files::CONFIG))
+			.into())
+		}
+		None => Ok(()),
+	}
+}
+
+type Plan<'a> = Vec<(&'a Branch, Vec<(PathBuf, PathBuf)>)>;
+
+// Every move is planned before anything is touched, so a clash is found up front
+pub fn plan<'a>(branches: &[&'a Branch], term: &str) -> Result<Plan<'a>> {
+	let root = Term::folder(term);
+	let mut moves: Vec<Vec<(PathBuf, PathBuf)>> = branches
+		.iter()
+		.map(|branch| {
+			[
+				&branch.path,
+				&branch.pdf_path,
+				&branch.html_path,
+				&branch.docx_path,
+				&branch.imgs_dir,
+				&branch.build_dir,
+			]
+			.into_iter()
+			.filter(|path| path.exists())
+			.map(|path| (path.clone(), root.join(path)))
+			.collect()
+		})
+		.collect();
+	branch::merge_moves(&mut moves)?;
+	if let Some((_, to)) = moves.iter().flatten().find(|(_, to)| to.exists()) {
+		return Err(
+			Error::new(Kind::Config, format!("{} already exists", to.display()))
+				.hint("move it out of the way before archiving")
+				.into(),
+		);
+	}
+	Ok(branches.iter().copied().zip(moves).collect())
+}
+
+// Everything moved so far is put back when a step or finish fails, so branches are either all
+// archived or not at all
+pub fn apply(
+	plan: &[(&Branch, Vec<(PathBuf, PathBuf)>)],
+	finish: impl FnOnce() -> Result<()>,
+) -> Result<()> {
+	let mut moved = Vec::new();
+	let result = (|| -> Result<()> {
+		for (branch, moves) in plan {
+			task(format!("Archiving {}", branch.name), || -> Result<()> {
+				for (from, to) in moves {
+					relayout::relocate(from, to).context("Failed to move file")?;
+					moved.push((from, to));
+				}
+				Ok(())
+			})
+			.context("Failed to archive branch")?;
+		}
+		finish()
+	})();
+
+	if result.is_err() {
+		for (from, to) in moved.into_iter().rev() {
+			let _ = relayout::relocate(to, from);
+		}
+	}
+	result
+}
+
+fn tag(term: &Term, count: usize) -> Result<()> {
+	let repo = git2::Repository::open(".").context("Failed to open repo")?;
+	let head = repo.head()?.peel_to_commit()?;
+	repo.tag(
+		&Term::tag(&term.name),
+		head.as_object(),
+		&repo.signature()?,
+		&format!(
+			"Archive {} ({} to {}, {} branches)",
+			term.name, term.start, term.end, count
+		),
+		false,
+	)
+	.context("Failed to create tag")?;
+	Ok(())
+}
@@ -1,21 +1,192 @@
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
 use clap::ArgMatches;
+use colorful::Colorful;
 use task_log::task;
 
 use crate::branch::Branch;
-use crate::conf::Config;
+use crate::conf::{Config, Engine, Target};
+use crate::diagnostic::{self, BuildError, Diagnostic, Severity};
+use crate::error::{Error, Kind};
+use crate::selector;
+
+pub fn run(args: &ArgMatches) -> Result<()> {
+	let config = Config::read().context("Failed to read from configuration file")?;
+	let engine = engine(args).context("Failed to parse engine")?;
+	let target = args
+		.value_of("to")
+		.map(Target::from_str)
+		.transpose()
+		.context("Failed to parse output target")?;
+
+	if args.is_present("all") {
+		let branches = task("Collecting branches", || -> Result<Vec<Branch>> {
+			selector::branches(&config, args).context("Failed to collect branches")
+		})?;
+		let jobs = match args.get_one::<usize>("jobs") {
+			Some(jobs) => *jobs,
+			None => thread::available_parallelism().map_or(1, |n| n.get()),
+		};
+		return build_all(
+			&config,
+			branches.iter().collect(),
+			jobs,
+			target.as_ref(),
+			engine.as_ref(),
+		);
+	}
 
-pub fn run(args: &ArgMatches) {
-	let config = Config::read().expect("Failed to read from configuration file");
+	let branch = task("Collecting branches", || {
+		selector::select(&config, args).context("Failed to select branch")
+	})?;
 
-	let branches = task("Collecting branches", || -> Vec<Branch> {
-		Branch::get_all(&config).expect("Failed to get all branches")
+	let result = task(format!("Building {}", branch.name), || {
+		match &target {
+			Some(target) => branch.build_target(&config, target, engine.as_ref()),
+			None => branch.build(&config, engine.as_ref()),
+		}
 	});
+	if let Err(err) = &result {
+		diagnostic::report(&branch, err, "  ");
+	}
+	result.context("Failed to build")
+}
+
+struct BuildResult<'a> {
+	branch: &'a Branch,
+	duration: Duration,
+	result: Result<()>,
+}
+
+pub fn engine(args: &ArgMatches) -> Result<Option<Engine>> {
+	args.value_of("engine").map(Engine::from_str).transpose()
+}
+
+pub fn build_all(
+	config: &Config,
+	branches: Vec<&Branch>,
+	jobs: usize,
+	target: Option<&Target>,
+	engine: Option<&Engine>,
+) -> Result<()> {
+	let total = branches.len();
+	let queue = Mutex::new(branches.into_iter());
+	let results = Mutex::new(Vec::new());
 
-	task("Building branch", || {
-		branches
-			.get(0)
-			.unwrap()
-			.build(&config, &(config.latexmk || args.is_present("latexmk")))
-			.expect("Failed to build");
+	println!(
+		"Building {} {} with {} {}\n",
+		total,
+		if total == 1 { "branch" } else { "branches" },
+		jobs,
+		if jobs == 1 { "job" } else { "jobs" }
+	);
+	thread::scope(|s| {
+		for _ in 0..jobs.clamp(1, total.max(1)) {
+			s.spawn(|| {
+				loop {
+					let branch = match queue.lock().unwrap().next() {
+						Some(branch) => branch,
+						None => break,
+					};
+					let start = Instant::now();
+					let result = match target {
+						Some(target) => branch.build_target(config, target, engine),
+						None => branch.build(config, engine),
+					};
+					let build_result = BuildResult {
+						branch,
+						duration: start.elapsed(),
+						result,
+					};
+
+					let mut results = results.lock().unwrap();
+					results.push(build_result);
+					let build_result = results.last().unwrap();
+					println!(
+						"  [{}/{}] {} {}",
+						results.len(),
+						total,
+						status(build_result),
+						build_result.branch.path.display()
+					);
+				}
+			});
+		}
 	});
+
+	let mut results = results.into_inner().unwrap();
+	results.sort_by_key(|r| r.result.is_err());
+	summarize(&results);
+	match results.iter().filter(|r| r.result.is_err()).count() {
+		0 => Ok(()),
+		failed => {
+			Err(Error::new(
+				Kind::Build,
+				format!(
+					"{} {} failed to build",
+					failed,
+					if failed == 1 { "branch" } else { "branches" }
+				),
+			)
+			.hint("run optic failures to see the logs")
+			.into())
+		}
+	}
+}
+
+fn status(build_result: &BuildResult) -> String {
+	if build_result.result.is_ok() {
+		format!("{:<6}", "DONE").green().to_string()
+	} else {
+		format!("{:<6}", "FAILED").red().to_string()
+	}
+}
+
+fn summarize(results: &[BuildResult]) {
+	if results.is_empty() {
+		println!("0 branches to build");
+		return;
+	}
+
+	let branch_width = results
+		.iter()
+		.map(|r| r.branch.to_string().chars().count())
+		.max()
+		.unwrap()
+		.max("BRANCH".len());
+	println!(
+		"\n  {:<6}  {:<branch_width$}  {:>8}",
+		"STATUS",
+		"BRANCH",
+		"TIME",
+		branch_width = branch_width
+	);
+	for build_result in results {
+		println!(
+			"  {}  {:<branch_width$}  {:>7.2}s",
+			status(build_result),
+			build_result.branch.to_string(),
+			build_result.duration.as_secs_f64(),
+			branch_width = branch_width
+		);
+		if let Err(err) = &build_result.result {
+			println!("          {:#}", err);
+			if let Some(build_error) = err.downcast_ref::<BuildError>() {
+				let errors: Vec<Diagnostic> = build_error
+					.diagnostics
+					.iter()
+					.filter(|d| d.severity == Severity::Error)
+					.cloned()
+					.collect();
+				diagnostic::print(build_result.branch, &errors, "          ");
+			}
+		}
+	}
+
+	let failed = results.iter().filter(|r| r.result.is_err()).count();
+	println!("\n{} succeeded, {} failed", results.len() - failed, failed);
 }
@@ -0,0 +1,175 @@
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::SystemTime;
+
+use anyhow::{Context, Result};
+use chrono::Local;
+use clap::ArgMatches;
+use colorful::Colorful;
+use task_log::task;
+
+use crate::branch::Branch;
+use crate::calendar::{self, Kind};
+use crate::cmd::new;
+use crate::conf::{Class, Config, DocumentType, Format};
+use crate::header::Status;
+use crate::locations::{self, files, folders};
+use crate::template::{BranchTemplate, RootTemplate};
+
+pub fn run(args: &ArgMatches) -> Result<()> {
+	match args.subcommand() {
+		Some(("export", args)) => export(args),
+		Some(("import", args)) => import(args),
+		_ => unreachable!(),
+	}
+}
+
+fn export(args: &ArgMatches) -> Result<()> {
+	let config = Config::read().context("Failed to read from configuration file")?;
+	let kind =
+		Kind::from_str(args.value_of("as").unwrap()).context("Failed to parse entry kind")?;
+	let output = locations::from_invocation(Path::new(args.value_of("output").unwrap()));
+
+	let branches = Branch::get_all(&config).context("Failed to get all branches")?;
+	let branches: Vec<&Branch> = branches
+		.iter()
+		.filter(|b| b.metadata.due.is_some())
+		.collect();
+	task(
+		format!(
+			"Writing {} branches to {}",
+			branches.len(),
+			output.display()
+		),
+		|| fs::write(&output, calendar::export(&branches, kind)),
+	)
+	.context("Failed to write calendar")
+}
+
+fn import(args: &ArgMatches) -> Result<()> {
+	let config = Config::read().context("Failed to read from configuration file")?;
+	let path = locations::from_invocation(Path::new(args.value_of("file").unwrap()));
+	let doc_type = DocumentType::from_str(args.value_of("type").unwrap())
+		.context("Failed to parse document type")?;
+	let class = args
+		.value_of("class")
+		.map(|name| {
+			config
+				.classes
+				.iter()
+				.find(|c| c.name == name)
+				.with_context(|| format!("{} is not a class in {}", name, files::CONFIG))
+		})
+		.transpose()?;
+
+	let assignments = task(format!("Reading {}", path.display()), || {
+		calendar::import(BufReader::new(
+			File::open(&path).context("Failed to open calendar file")?,
+		))
+		.context("Failed to parse calendar file")
+	})?;
+
+	let today = Local::now().date_naive();
+	let (mut created, mut skipped) = (0, 0);
+	for assignment in assignments {
+		if assignment.due < today && !args.is_present("past") {
+			skipped += 1;
+			continue;
+		}
+		let class = match class.or_else(|| assignment.class(&config.classes)) {
+			Some(class) => class,
+			None => {
+				println!(
+					"  {} {} (no class matches {})",
+					"SKIPPED".yellow(),
+					assignment.name,
+					assignment.courses.join(", ")
+				);
+				skipped += 1;
+				continue;
+			}
+		};
+
+		let (format, branch_template, root_template) =
+			defaults(&config, class).context("Failed to find default templates for class")?;
+		let mut branch = Branch::new(
+			assignment.name,
+			format,
+			doc_type,
+			class.clone(),
+			Some(branch_template),
+			root_template,
+			today,
+			SystemTime::now(),
+			&config.layout,
+		)
+		.context("Failed to create branch")?;
+		if branch.path.exists() {
+			skipped += 1;
+			continue;
+		}
+		branch.metadata.due = Some(assignment.due);
+		branch.metadata.status = Some(Status::Todo);
+
+		task(format!("Creating {}", branch.path.display()), || {
+			new::create(&config, &branch)
+		})
+		.context("Failed to create branch")?;
+		created += 1;
+	}
+	println!("\n{} created, {} skipped", created, skipped);
+	Ok(())
+}
+
+fn defaults(config: &Config, class: &Class) -> Result<(Format, BranchTemplate, RootTemplate)> {
+	let format = class
+		.format
+		.clone()
+		.unwrap_or_else(|| config.default_format.clone());
+
+	let mut branch_templates: Vec<BranchTemplate> = BranchTemplate::get_all()?
+		.into_iter()
+		.filter(|t| t.format == format)
+		.collect();
+	branch_templates.sort_by(|a, b| a.name.cmp(&b.name));
+	let branch_template = match &class.branch_template {
+		Some(name) => branch_templates.into_iter().find(|t| &t.name == name),
+		None => branch_templates.into_iter().next(),
+	}
+	.with_context(|| {
+		format!(
+			"No {} branch template for {} in {}",
+			format,
+			class.name,
+			Path::new(folders::TEMPLATES)
+				.join(folders::BRANCH_TEMPLATES)
+				.display()
+		)
+	})?;
+
+	let mut root_templates: Vec<RootTemplate> = RootTemplate::get_all()?
+		.into_iter()
+		.filter(|r| {
+			Format::from_path(Path::new(&r.name)).unwrap_or_default()
+				== format.plugin().root_format()
+		})
+		.collect();
+	root_templates.sort_by(|a, b| a.name.cmp(&b.name));
+	let root_template = match &class.root_template {
+		Some(name) => root_templates.into_iter().find(|t| &t.name == name),
+		None => root_templates.into_iter().next(),
+	}
+	.with_context(|| {
+		format!(
+			"No root template for {} in {}",
+			class.name,
+			Path::new(folders::TEMPLATES)
+				.join(folders::ROOT_TEMPLATES)
+				.display()
+		)
+	})?;
+
+	Ok((format, branch_template, root_template))
+}
@@ -1,71 +1,94 @@
+use std::thread;
 use std::time::UNIX_EPOCH;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use chrono::Duration;
 use chrono_humanize::{Accuracy, HumanTime, Tense};
 use dialoguer::theme::ColorfulTheme;
 use dialoguer::Confirm;
-use task_log::task;
 
 use crate::branch::Branch;
-use crate::conf::Config;
+use crate::cmd::build;
+use crate::conf::{Config, Target};
 
-pub fn run() {
-	let config = Config::read().expect("Failed to read from configuration file");
-	let branches = Branch::get_all(&config).expect("Failed to get all branches");
-	let (missing_pdfs, old_pdfs) =
-		needs_building(&branches).expect("Failed to get branches that need building");
-	let (build_missing, build_old) =
-		ask(&missing_pdfs, &old_pdfs).expect("Failed to ask user about old and missing pdfs");
+type Outputs<'a> = Vec<(&'a Branch, Target)>;
+
+pub fn run() -> Result<()> {
+	let config = Config::read().context("Failed to read from configuration file")?;
+	let branches = Branch::get_all(&config).context("Failed to get all branches")?;
+	let (missing_outputs, old_outputs) =
+		needs_building(&config, &branches).context("Failed to get branches that need building")?;
+	let (build_missing, build_old) = ask(&missing_outputs, &old_outputs)
+		.context("Failed to ask user about old and missing outputs")?;
 
 	if build_missing || build_old {
 		println!();
 	}
-	if build_missing {
-		build_all(&config, missing_pdfs);
+	let jobs = thread::available_parallelism().map_or(1, |n| n.get());
+	let missing_result = if build_missing {
+		build::build_all(&config, unique(&missing_outputs), jobs, None, None)
 	} else {
-		println!("0 branches with missing PDF files");
-	}
-	if build_old {
-		build_all(&config, old_pdfs);
+		println!("0 branches with missing output files");
+		Ok(())
+	};
+	let old_result = if build_old {
+		build::build_all(&config, unique(&old_outputs), jobs, None, None)
 	} else {
-		println!("0 branches with old PDF files");
-	}
+		println!("0 branches with old output files");
+		Ok(())
+	};
+	missing_result.and(old_result)
 }
 
-pub fn needs_building(branches: &Vec<Branch>) -> Result<(Vec<&Branch>, Vec<&Branch>)> {
-	let mut missing_pdf = Vec::new();
-	let mut old_pdfs = Vec::new();
+pub fn needs_building<'a>(
+	config: &Config,
+	branches: &'a Vec<Branch>,
+) -> Result<(Outputs<'a>, Outputs<'a>)> {
+	let mut missing_outputs = Vec::new();
+	let mut old_outputs = Vec::new();
 	for branch in branches {
-		if branch.pdf_path.exists() {
-			if branch.pdf_path.metadata()?.modified()? < branch.mod_time {
-				old_pdfs.push(branch);
+		for target in branch.targets(config) {
+			let output_path = branch.output_path(&target);
+			if output_path.exists() {
+				if output_path.metadata()?.modified()? < branch.mod_time {
+					old_outputs.push((branch, target));
+				}
+			} else {
+				missing_outputs.push((branch, target));
 			}
-		} else {
-			missing_pdf.push(branch);
 		}
 	}
 
-	Ok((missing_pdf, old_pdfs))
+	Ok((missing_outputs, old_outputs))
+}
+
+fn unique<'a>(outputs: &[(&'a Branch, Target)]) -> Vec<&'a Branch> {
+	let mut branches: Vec<&Branch> = Vec::new();
+	for (branch, _) in outputs {
+		if !branches.iter().any(|b| b.path == branch.path) {
+			branches.push(branch);
+		}
+	}
+	branches
 }
 
-pub fn ask(missing_pdfs: &Vec<&Branch>, old_pdfs: &Vec<&Branch>) -> Result<(bool, bool)> {
+pub fn ask(missing_outputs: &Outputs, old_outputs: &Outputs) -> Result<(bool, bool)> {
 	let mut build_missing = false;
 	let mut build_old = false;
 	let theme = ColorfulTheme::default();
 
-	if !missing_pdfs.is_empty() {
+	if !missing_outputs.is_empty() {
 		println!(
 			"The following {}:\n",
-			if missing_pdfs.len() == 1 {
-				"branch is missing a PDF"
+			if missing_outputs.len() == 1 {
+				"output is missing"
 			} else {
-				"branches are missing PDFs"
+				"outputs are missing"
 			}
 		);
 
-		for branch in missing_pdfs {
-			println!("\t{} ({})", branch.name, branch.path.display());
+		for (branch, target) in missing_outputs {
+			println!("\t{} ({}) [{}]", branch.name, branch.path.display(), target);
 		}
 		println!();
 
@@ -74,24 +97,25 @@ pub fn ask(missing_pdfs: &Vec<&Branch>, old_pdfs: &Vec<&Branch>) -> Result<(bool
 			.interact()?;
 	}
 
-	if !old_pdfs.is_empty() {
+	if !old_outputs.is_empty() {
 		println!(
 			"\nThe following {}:\n",
-			if old_pdfs.len() == 1 {
-				"branch has an old a PDF"
+			if old_outputs.len() == 1 {
+				"output is old"
 			} else {
-				"branches have old PDFs"
+				"outputs are old"
 			}
 		);
 
-		for branch in old_pdfs {
+		for (branch, target) in old_outputs {
 			println!(
-				"\t{} ({})\n\t\tΔ age: {}",
+				"\t{} ({}) [{}]\n\t\tΔ age: {}",
 				branch.name,
 				branch.path.display(),
+				target,
 				HumanTime::from(Duration::seconds(
 					(branch
-						.pdf_path
+						.output_path(target)
 						.metadata()?
 						.modified()?
 						.duration_since(UNIX_EPOCH)
@@ -114,13 +138,3 @@ pub fn ask(missing_pdfs: &Vec<&Branch>, old_pdfs: &Vec<&Branch>) -> Result<(bool
 
 	Ok((build_missing, build_old))
 }
-
-pub fn build_all(config: &Config, branches: Vec<&Branch>) {
-	for branch in branches {
-		task(format!("Building {}", branch.path.display()), || {
-			branch
-				.build(config, &config.latexmk)
-				.expect("Failed to build branch");
-		})
-	}
-}
@@ -0,0 +1,378 @@
+use std::path::PathBuf;
+use std::{fs, slice};
+
+use anyhow::{bail, Context, Result};
+use chrono::{Duration, Local};
+use chrono_humanize::HumanTime;
+use clap::ArgMatches;
+use colorful::Colorful;
+use dialoguer::theme::ColorfulTheme;
+use dialoguer::Input;
+use task_log::task;
+
+use crate::branch::{self, Branch};
+use crate::classes;
+use crate::cmd::{archive, relayout};
+use crate::conf::{Class, Config, Layers};
+use crate::error::{Error, Kind};
+use crate::locations::files;
+
+pub fn run(args: &ArgMatches) -> Result<()> {
+	let layers = Layers::read().context("Failed to read from configuration file")?;
+	let config = layers
+		.config()
+		.context("Failed to read from configuration file")?;
+	match args.subcommand() {
+		Some(("add", args)) => add(&layers, &config, args),
+		Some(("list", _)) => list(&config),
+		Some(("rename", args)) => rename(&layers, &config, args),
+		Some(("deactivate", args)) => set_active(&layers, &config, args, false),
+		Some(("activate", args)) => set_active(&layers, &config, args, true),
+		Some(("remove", args)) => remove(&layers, &config, args),
+		_ => unreachable!(),
+	}
+}
+
+fn add(layers: &Layers, config: &Config, args: &ArgMatches) -> Result<()> {
+	let theme = ColorfulTheme::default();
+	let name = match args.value_of("name") {
+		Some(name) => name.trim().to_string(),
+		None => {
+			Input::<String>::with_theme(&theme)
+				.with_prompt("Class name")
+				.interact_text()?
+				.trim()
+				.to_string()
+		}
+	};
+	check_name(config, &name, None)?;
+	let teacher = match args.value_of("teacher") {
+		Some(teacher) => teacher.trim().to_string(),
+		None => {
+			Input::<String>::with_theme(&theme)
+				.with_prompt("Teacher")
+				.interact_text()?
+				.trim()
+				.to_string()
+		}
+	};
+
+	save(layers, &classes::add(&layers.content, &name, &teacher)?)?;
+	println!("Added {} ({})", name, teacher);
+	Ok(())
+}
+
+fn list(config: &Config) -> Result<()> {
+	if config.classes.is_empty() {
+		println!("0 classes");
+		return Ok(());
+	}
+	let branches = Branch::get_all(config).context("Failed to get all branches")?;
+	let today = Local::now().date_naive();
+
+	let rows: Vec<[String; 5]> = config
+		.classes
+		.iter()
+		.map(|class| {
+			let branches: Vec<&Branch> = branches
+				.iter()
+				.filter(|b| b.class.name == class.name)
+				.collect();
+			let last_activity = branches
+				.iter()
+				.map(|b| b.mod_time)
+				.max()
+				.and_then(|t| t.elapsed().ok())
+				.map(|age| HumanTime::from(-Duration::seconds(age.as_secs() as i64)).to_string())
+				.unwrap_or_else(|| String::from("never"));
+			[
+				class.name.clone(),
+				class.teacher.clone(),
+				if class.is_active(&config.terms, today) {
+					String::from("yes")
+				} else {
+					String::from("no")
+				},
+				branches.len().to_string(),
+				last_activity,
+			]
+		})
+		.collect();
+
+	let headers = ["CLASS", "TEACHER", "ACTIVE", "BRANCHES", "LAST ACTIVITY"];
+	let widths: Vec<usize> = (0..headers.len())
+		.map(|i| {
+			rows.iter()
+				.map(|r| r[i].chars().count())
+				.chain([headers[i].len()])
+				.max()
+				.unwrap()
+		})
+		.collect();
+	println!(
+		"\n  {:<w0$}  {:<w1$}  {:<w2$}  {:>w3$}  {}",
+		headers[0],
+		headers[1],
+		headers[2],
+		headers[3],
+		headers[4],
+		w0 = widths[0],
+		w1 = widths[1],
+		w2 = widths[2],
+		w3 = widths[3]
+	);
+	for row in &rows {
+		let line = format!(
+			"  {}  {:<w1$}  {:<w2$}  {:>w3$}  {}",
+			format!("{:<w0$}", row[0], w0 = widths[0]).bold(),
+			row[1],
+			row[2],
+			row[3],
+			row[4],
+			w1 = widths[1],
+			w2 = widths[2],
+			w3 = widths[3]
+		);
+		if row[2] == "yes" {
+			println!("{}", line);
+		} else {
+			println!("{}", line.dim());
+		}
+	}
+	println!();
+	Ok(())
+}
+
+fn rename(layers: &Layers, config: &Config, args: &ArgMatches) -> Result<()> {
+	let old = class(config, args.value_of("old").unwrap())?;
+	let new_name = args.value_of("new").unwrap().trim();
+	check_name(config, new_name, Some(old))?;
+	let renamed = Class {
+		name: new_name.to_string(),
+		..old.clone()
+	};
+	let content = classes::rename(&layers.content, &old.name, new_name)?;
+	check(layers, &content)?;
+
+	let branches: Vec<Branch> = Branch::get_all(config)
+		.context("Failed to get all branches")?
+		.into_iter()
+		.filter(|b| b.class.name == old.name)
+		.collect();
+	let mut moves: Vec<(PathBuf, PathBuf)> = Vec::new();
+	for branch in &branches {
+		let new = branch
+			.relocated(&renamed, &config.layout)
+			.context("Failed to lay out branch")?;
+		moves.extend(
+			[
+				(&branch.path, new.path),
+				(&branch.pdf_path, new.pdf_path),
+				(&branch.html_path, new.html_path),
+				(&branch.docx_path, new.docx_path),
+				(&branch.imgs_dir, new.imgs_dir),
+				(&branch.build_dir, new.build_dir),
+			]
+			.into_iter()
+			.filter(|(from, to)| from != &to && from.exists())
+			.map(|(from, to)| (from.clone(), to)),
+		);
+	}
+	branch::merge_moves(slice::from_mut(&mut moves))?;
+	if let Some((_, to)) = moves.iter().find(|(_, to)| to.exists()) {
+		return Err(
+			Error::new(Kind::Config, format!("{} already exists", to.display()))
+				.hint("move it out of the way or pick another name")
+				.into(),
+		);
+	}
+
+	task(format!("Renaming {} to {}", old.name, new_name), || {
+		apply(&moves, &content)
+	})
+	.context("Failed to rename class")?;
+	println!(
+		"Renamed {} to {} ({} {})",
+		old.name,
+		new_name,
+		branches.len(),
+		if branches.len() == 1 {
+			"branch"
+		} else {
+			"branches"
+		}
+	);
+	Ok(())
+}
+
+// Everything done so far is undone when a step fails, so a rename either fully happens or not
+// at all. Branch headers keep the old name, which the config remembers in renamed_from
+fn apply(moves: &[(PathBuf, PathBuf)], content: &str) -> Result<()> {
+	let mut moved = Vec::new();
+	let result = (|| -> Result<()> {
+		for (from, to) in moves {
+			relayout::relocate(from, to).context("Failed to move file")?;
+			moved.push((from, to));
+		}
+		fs::write(files::CONFIG, content).context("Failed to write configuration file")?;
+		Ok(())
+	})();
+
+	if result.is_err() {
+		for (from, to) in moved.into_iter().rev() {
+			let _ = relayout::relocate(to, from);
+		}
+	}
+	result
+}
+
+fn set_active(layers: &Layers, config: &Config, args: &ArgMatches, active: bool) -> Result<()> {
+	let class = class(config, args.value_of("name").unwrap())?;
+	let today = Local::now().date_naive();
+	// Leave the key out when the class's terms already give the right answer
+	let inferred = Class {
+		active: None,
+		..class.clone()
+	}
+	.is_active(&config.terms, today);
+	let value = if active && inferred {
+		None
+	} else {
+		Some(active)
+	};
+
+	save(
+		layers,
+		&classes::set_active(&layers.content, &class.name, value)?,
+	)?;
+	println!(
+		"{} is now {}",
+		class.name,
+		if active { "active" } else { "inactive" }
+	);
+	Ok(())
+}
+
+fn remove(layers: &Layers, config: &Config, args: &ArgMatches) -> Result<()> {
+	let class = class(config, args.value_of("name").unwrap())?;
+	let content = classes::remove(&layers.content, &class.name)?;
+	check(layers, &content)?;
+	let branches: Vec<Branch> = Branch::get_all(config)
+		.context("Failed to get all branches")?
+		.into_iter()
+		.filter(|b| b.class.name == class.name && b.archived.is_none())
+		.collect();
+
+	if !branches.is_empty() {
+		if !args.is_present("archive") {
+			return Err(Error::new(
+				Kind::Config,
+				format!(
+					"{} still has {} {}",
+					class.name,
+					branches.len(),
+					if branches.len() == 1 {
+						"branch"
+					} else {
+						"branches"
+					}
+				),
+			)
+			.hint("pass --archive to archive them first")
+			.into());
+		}
+		// Archived branches are found again by term, so they can only go into one from the config
+		let term = match args.value_of("term") {
+			Some(name) => config.term(name)?,
+			None => {
+				config
+					.terms
+					.iter()
+					.filter(|t| class.terms.contains(&t.name))
+					.max_by_key(|t| t.end)
+					.with_context(|| {
+						Error::new(
+							Kind::Config,
+							format!("{} has no terms to archive its branches into", class.name),
+						)
+						.hint(format!(
+							"pass --term with one of the [[terms]] in {}",
+							files::CONFIG
+						))
+					})?
+			}
+		};
+		archive::check_term(term)?;
+		let branches: Vec<&Branch> = branches.iter().collect();
+		// The class only leaves the config once its branches are safely archived
+		archive::apply(&archive::plan(&branches, &term.name)?, || {
+			fs::write(files::CONFIG, &content).context("Failed to write configuration file")
+		})?;
+	} else {
+		fs::write(files::CONFIG, content).context("Failed to write configuration file")?;
+	}
+	println!("Removed {}", class.name);
+	Ok(())
+}
+
+fn class<'a>(config: &'a Config, name: &str) -> Result<&'a Class> {
+	config
+		.classes
+		.iter()
+		.find(|c| c.name == name)
+		.with_context(|| {
+			Error::new(
+				Kind::Config,
+				format!("{} is not a class in {}", name, files::CONFIG),
+			)
+			.hint("run optic class list to see every class")
+		})
+}
+
+fn check_name(config: &Config, name: &str, renaming: Option<&Class>) -> Result<()> {
+	if name.is_empty() {
+		bail!("Class name can't be empty");
+	}
+	// Class names become folder names in most layouts
+	if name.contains(['/', '\\']) {
+		bail!("Class name can't contain slashes");
+	}
+	if config.classes.iter().any(|c| c.name == name) {
+		return Err(
+			Error::new(Kind::Config, format!("{} is already a class", name))
+				.hint("pick another name")
+				.into(),
+		);
+	}
+	// Branches that still use an old name have to keep finding the class it belonged to
+	if let Some(other) = config
+		.classes
+		.iter()
+		.filter(|c| renaming.is_none_or(|r| r.name != c.name))
+		.find(|c| c.renamed_from.iter().any(|n| n == name))
+	{
+		return Err(Error::new(
+			Kind::Config,
+			format!("{} used to be the name of {}", name, other.name),
+		)
+		.hint("pick another name")
+		.into());
+	}
+	Ok(())
+}
+
+// The edited config has to still parse, including any values from the global config
+fn check(layers: &Layers, content: &str) -> Result<()> {
+	Layers {
+		content: content.to_string(),
+		project: toml::from_str(content).context("Failed to parse edited configuration")?,
+		global: layers.global.clone(),
+	}
+	.config()?;
+	Ok(())
+}
+
+fn save(layers: &Layers, content: &str) -> Result<()> {
+	check(layers, content)?;
+	fs::write(files::CONFIG, content).context("Failed to write configuration file")
+}
@@ -8,16 +8,18 @@ use task_log::task;
 use crate::branch::Branch;
 use crate::conf::Config;
 
-pub fn run() {
-	let config = Config::read().expect("Failed to read configuration");
-	let branches = Branch::get_all(&config).expect("Failed to get all branches");
-	let branches_to_commit = working_branches(branches).expect("Failed to get working branches");
+pub fn run() -> Result<()> {
+	let config = Config::read().context("Failed to read configuration")?;
+	let branches = Branch::get_all(&config).context("Failed to get all branches")?;
+	let branches_to_commit =
+		working_branches(branches).context("Failed to get working branches")?;
 	for (msg, branch) in branches_to_commit {
 		task(format!("Committing {}", branch.name), || {
 			commit_branch(msg, &branch)
-				.unwrap_or_else(|_| panic!("Failed to commit {}", branch.name));
 		})
+		.with_context(|| format!("Failed to commit {}", branch.name))?;
 	}
+	Ok(())
 }
 
 fn working_branches(branches: Vec<Branch>) -> Result<HashMap<String, Branch>> {
@@ -61,6 +63,12 @@ fn commit_branch(msg: String, branch: &Branch) -> Result<()> {
 	if branch.pdf_path.exists() {
 		stage_cmd.arg(&branch.pdf_path);
 	}
+	if branch.html_path.exists() {
+		stage_cmd.arg(&branch.html_path);
+	}
+	if branch.docx_path.exists() {
+		stage_cmd.arg(&branch.docx_path);
+	}
 	if branch.imgs_dir.exists() {
 		stage_cmd.arg(&branch.imgs_dir);
 	}
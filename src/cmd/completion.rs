@@ -1,16 +1,18 @@
 use std::io;
 
+use anyhow::Result;
 use clap::ArgMatches;
 use clap_complete::{generate, Shell};
 
 use crate::cli;
 
-pub fn run(args: &ArgMatches) {
+pub fn run(args: &ArgMatches) -> Result<()> {
 	let mut command = cli::setup();
 	generate(
-		args.value_of_t::<Shell>("shell").expect("Invalid shell"),
+		args.value_of_t::<Shell>("shell")?,
 		&mut command,
 		cli::setup().get_name().to_string(),
 		&mut io::stdout(),
 	);
+	Ok(())
 }
@@ -0,0 +1,91 @@
+use anyhow::{Context, Result};
+use clap::ArgMatches;
+use colorful::Colorful;
+use toml::value::{Table, Value};
+
+use crate::conf::Layers;
+use crate::error::{Error, Kind};
+use crate::validate;
+
+pub fn run(args: &ArgMatches) -> Result<()> {
+	match args.subcommand() {
+		Some(("show", _)) => show(),
+		Some(("check", _)) => check(),
+		_ => unreachable!(),
+	}
+}
+
+fn show() -> Result<()> {
+	let layers = Layers::read().context("Failed to read configuration")?;
+	let config = layers.config()?;
+	let effective = match Value::try_from(&config).context("Failed to serialize configuration")? {
+		Value::Table(table) => table,
+		_ => unreachable!(),
+	};
+
+	// Plain values have to come before tables for the output to stay valid TOML
+	let (tables, values): (Vec<_>, Vec<_>) =
+		effective.iter().partition(|(_, value)| is_table(value));
+	for (key, value) in values {
+		println!(
+			"{}  # {}",
+			snippet(key, value)?.trim_end(),
+			layers.source(key)
+		);
+	}
+	for (key, value) in tables {
+		print!("\n# {}\n{}", layers.source(key), snippet(key, value)?);
+	}
+	Ok(())
+}
+
+fn check() -> Result<()> {
+	let problems = validate::check().context("Failed to check configuration")?;
+	if problems.is_empty() {
+		println!("{}", "No problems found".green());
+		return Ok(());
+	}
+
+	for problem in &problems {
+		let location = match problem.position {
+			Some((line, column)) => format!("{}:{}:{}", problem.file.display(), line, column),
+			None => problem.file.display().to_string(),
+		};
+		println!(
+			"{} {} {}",
+			location.bold(),
+			"error:".red().bold(),
+			problem.message
+		);
+		if let Some(fix) = &problem.fix {
+			println!("  {} {}", "fix:".cyan().bold(), fix);
+		}
+	}
+	Err(Error::new(
+		Kind::Config,
+		format!(
+			"Found {} {}",
+			problems.len(),
+			if problems.len() == 1 {
+				"problem"
+			} else {
+				"problems"
+			}
+		),
+	)
+	.into())
+}
+
+fn is_table(value: &Value) -> bool {
+	match value {
+		Value::Table(_) => true,
+		Value::Array(items) => !items.is_empty() && items.iter().all(Value::is_table),
+		_ => false,
+	}
+}
+
+fn snippet(key: &str, value: &Value) -> Result<String> {
+	let mut table = Table::new();
+	table.insert(key.to_string(), value.clone());
+	toml::to_string(&table).with_context(|| format!("Failed to serialize {}", key))
+}
@@ -0,0 +1,48 @@
+use std::time::SystemTime;
+
+use anyhow::{Context, Result};
+use chrono::Duration;
+use chrono_humanize::{Accuracy, HumanTime, Tense};
+
+use crate::branch::Branch;
+use crate::conf::Config;
+
+pub fn run() -> Result<()> {
+	let config = Config::read().context("Failed to read from configuration file")?;
+	let branches = Branch::get_all(&config).context("Failed to get all branches")?;
+	let failed: Vec<_> = branches
+		.iter()
+		.filter_map(|b| b.failure_log().map(|log| (b, log)))
+		.collect();
+
+	if failed.is_empty() {
+		println!("0 branches with failed builds");
+		return Ok(());
+	}
+
+	println!(
+		"The following {}:\n",
+		if failed.len() == 1 {
+			"branch failed to build"
+		} else {
+			"branches failed to build"
+		}
+	);
+	for (branch, log) in failed {
+		let age = log
+			.metadata()
+			.and_then(|m| m.modified())
+			.ok()
+			.and_then(|t| SystemTime::now().duration_since(t).ok())
+			.unwrap_or_default();
+		println!(
+			"\t{} ({})\n\t\tlog: {}\n\t\tfailed: {}",
+			branch.name,
+			branch.path.display(),
+			log.display(),
+			HumanTime::from(-Duration::seconds(age.as_secs() as i64))
+				.to_text_en(Accuracy::Rough, Tense::Past)
+		);
+	}
+	Ok(())
+}
@@ -0,0 +1,123 @@
+use anyhow::{Context, Result};
+use clap::ArgMatches;
+use colorful::Colorful;
+
+use crate::branch::Branch;
+use crate::conf::Config;
+use crate::grades::{self, Report};
+
+pub fn run(args: &ArgMatches) -> Result<()> {
+	let config = Config::read().context("Failed to read from configuration file")?;
+	let branches = Branch::get_all(&config).context("Failed to get all branches")?;
+	let report = grades::report(&config.classes, &branches, &config.grade_scale);
+
+	match args.value_of("format").unwrap() {
+		"json" => {
+			println!(
+				"{}",
+				serde_json::to_string_pretty(&report).context("Failed to serialize grades")?
+			)
+		}
+		"csv" => print!("{}", csv(&report)),
+		_ => table(&report),
+	}
+	// Kept off stdout so JSON and CSV output can still be piped
+	for warning in &report.warnings {
+		eprintln!("{} {}", "warning:".yellow().bold(), warning);
+	}
+	Ok(())
+}
+
+fn table(report: &Report) {
+	if report.classes.is_empty() {
+		println!("0 graded branches");
+		return;
+	}
+
+	let class_width = report
+		.classes
+		.iter()
+		.flat_map(|c| {
+			c.categories
+				.iter()
+				.map(|category| category.doc_type.chars().count() + 2)
+				.chain([c.class.chars().count()])
+		})
+		.max()
+		.unwrap()
+		.max("CLASS".len());
+	println!(
+		"\n  {:<class_width$}  {:>8}  {:<5}  {:>6}",
+		"CLASS",
+		"AVERAGE",
+		"GRADE",
+		"POINTS",
+		class_width = class_width
+	);
+	for class in &report.classes {
+		println!(
+			"  {}  {:>7.2}%  {:<5}  {:>6.2}",
+			format!("{:<class_width$}", class.class, class_width = class_width).bold(),
+			class.average,
+			class.letter,
+			class.points
+		);
+		for category in &class.categories {
+			println!(
+				"  {}",
+				format!(
+					"  {:<width$}  {:>7.2}%  {} graded{}",
+					category.doc_type,
+					category.average,
+					category.graded,
+					category
+						.weight
+						.map(|w| format!(", weighted {}%", w))
+						.unwrap_or_default(),
+					width = class_width - 2
+				)
+				.dim()
+			);
+		}
+	}
+	if let Some(gpa) = report.gpa {
+		println!("\n  GPA {:.2}", gpa);
+	}
+}
+
+fn csv(report: &Report) -> String {
+	let mut output = String::from("class,type,graded,weight,credits,average,letter,points\n");
+	for class in &report.classes {
+		for category in &class.categories {
+			output.push_str(&format!(
+				"{},{},{},{},,{:.2},,\n",
+				field(&class.class),
+				field(&category.doc_type),
+				category.graded,
+				category.weight.map(|w| w.to_string()).unwrap_or_default(),
+				category.average
+			));
+		}
+		output.push_str(&format!(
+			"{},,{},,{},{:.2},{},{:.2}\n",
+			field(&class.class),
+			class.categories.iter().map(|c| c.graded).sum::<usize>(),
+			class.credits,
+			class.average,
+			field(&class.letter),
+			class.points
+		));
+	}
+	if let Some(gpa) = report.gpa {
+		output.push_str(&format!("GPA,,,,,,,{:.2}\n", gpa));
+	}
+	output
+}
+
+fn field(value: &str) -> String {
+	if value.contains([',', '"', '\n']) {
+		format!("\"{}\"", value.replace('"', "\"\""))
+	} else {
+		value.to_string()
+	}
+}
@@ -0,0 +1,193 @@
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::{bail, Context, Result};
+use clap::ArgMatches;
+use dialoguer::theme::ColorfulTheme;
+use dialoguer::{Confirm, Input, Select};
+use task_log::task;
+
+use crate::conf::Format;
+use crate::error::{Error, Kind};
+use crate::locations::{files, folders};
+use crate::starter;
+
+struct Answers {
+	author: String,
+	format: Format,
+	classes: Vec<(String, String)>,
+	git: bool,
+}
+
+pub fn run(args: &ArgMatches) -> Result<()> {
+	let dir = Path::new(args.value_of("dir").unwrap());
+	let config_path = dir.join(files::CONFIG);
+	if config_path.exists() {
+		return Err(Error::new(
+			Kind::Config,
+			format!("{} already exists", config_path.display()),
+		)
+		.hint("run optic config check to validate it")
+		.into());
+	}
+	let answers = ask(dir, args).context("Failed to ask user about project")?;
+	println!();
+
+	task("Creating folders", || create_folders(dir)).context("Failed to create folders")?;
+	task(format!("Writing {}", files::CONFIG), || -> Result<()> {
+		fs::write(
+			&config_path,
+			starter::config(&answers.author, &answers.format, &answers.classes)?,
+		)?;
+		Ok(())
+	})
+	.context("Failed to write configuration file")?;
+	task("Installing starter templates", || install_templates(dir))
+		.context("Failed to install starter templates")?;
+	task("Writing .gitignore", || gitignore(dir)).context("Failed to write .gitignore")?;
+	if answers.git {
+		task("Initializing git repository", || {
+			git2::Repository::init(dir)
+		})
+		.context("Failed to initialize git repository")?;
+	}
+
+	println!(
+		"\nCreated an optic project in {}. Run optic new to create your first branch",
+		dir.canonicalize()
+			.context("Failed to find project folder")?
+			.display()
+	);
+	Ok(())
+}
+
+fn ask(dir: &Path, args: &ArgMatches) -> Result<Answers> {
+	let theme = ColorfulTheme::default();
+	let interactive = !args.is_present("yes");
+
+	let author = match args.value_of("author") {
+		Some(author) => author.to_string(),
+		None => {
+			let git_name = git2::Config::open_default()
+				.and_then(|c| c.get_string("user.name"))
+				.ok();
+			match (interactive, git_name) {
+				(true, git_name) => {
+					let mut input = Input::<String>::with_theme(&theme);
+					input.with_prompt("Author");
+					if let Some(name) = git_name {
+						input.default(name);
+					}
+					input.interact_text()?
+				}
+				(false, Some(name)) => name,
+				(false, None) => bail!("No author given. Pass --author or set user.name in git"),
+			}
+		}
+	};
+
+	let format = match args.value_of("format") {
+		Some(format) => format,
+		None if interactive => {
+			starter::FORMATS[Select::with_theme(&theme)
+				.with_prompt("Default format")
+				.items(&starter::FORMATS)
+				.default(1)
+				.interact()?]
+		}
+		None => starter::FORMATS[1],
+	};
+	let format = Format::from_str(format)?;
+
+	let mut classes = Vec::new();
+	if let Some(values) = args.values_of("class") {
+		for value in values {
+			let (name, teacher) = value
+				.split_once(':')
+				.with_context(|| format!("{} is not in the NAME:TEACHER format", value))?;
+			classes.push((name.trim().to_string(), teacher.trim().to_string()));
+		}
+	} else if interactive {
+		loop {
+			let name: String = Input::with_theme(&theme)
+				.with_prompt("Class name (leave empty to finish)")
+				.allow_empty(true)
+				.interact_text()?;
+			if name.trim().is_empty() {
+				break;
+			}
+			let teacher: String = Input::with_theme(&theme)
+				.with_prompt("Teacher")
+				.interact_text()?;
+			classes.push((name.trim().to_string(), teacher.trim().to_string()));
+		}
+	}
+
+	let in_repo = git2::Repository::discover(dir).is_ok();
+	let git = if args.is_present("git") {
+		true
+	} else if args.is_present("no-git") || in_repo {
+		false
+	} else if interactive {
+		Confirm::with_theme(&theme)
+			.with_prompt("Initialize a git repository?")
+			.default(true)
+			.interact()?
+	} else {
+		true
+	};
+
+	Ok(Answers {
+		author,
+		format,
+		classes,
+		git,
+	})
+}
+
+fn create_folders(dir: &Path) -> Result<()> {
+	for folder in [
+		folders::BRANCHES,
+		folders::PDFS,
+		folders::HTML,
+		folders::DOCX,
+		folders::IMAGES,
+		folders::ARCHIVE,
+	] {
+		fs::create_dir_all(dir.join(folder))?;
+	}
+	for folder in [folders::BRANCH_TEMPLATES, folders::ROOT_TEMPLATES] {
+		fs::create_dir_all(dir.join(folders::TEMPLATES).join(folder))?;
+	}
+	Ok(())
+}
+
+fn install_templates(dir: &Path) -> Result<()> {
+	for (folder, name, content) in starter::TEMPLATES {
+		let path = dir.join(folders::TEMPLATES).join(folder).join(name);
+		// Keep templates the user already has
+		if !path.exists() {
+			fs::write(path, content)?;
+		}
+	}
+	Ok(())
+}
+
+fn gitignore(dir: &Path) -> Result<()> {
+	let path = dir.join(".gitignore");
+	let mut content = fs::read_to_string(&path).unwrap_or_default();
+	if content
+		.lines()
+		.any(|l| l.trim().trim_end_matches('/') == folders::BUILD)
+	{
+		return Ok(());
+	}
+	if !content.is_empty() && !content.ends_with('\n') {
+		content.push('\n');
+	}
+	content.push_str(starter::GITIGNORE);
+	content.push('\n');
+	fs::write(path, content)?;
+	Ok(())
+}
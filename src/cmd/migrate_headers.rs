@@ -0,0 +1,43 @@
+use std::fs::{self, File};
+
+use anyhow::{Context, Result};
+use task_log::task;
+
+use crate::branch::Branch;
+use crate::conf::Config;
+use crate::header;
+
+pub fn run() -> Result<()> {
+	let config = Config::read().context("Failed to read from configuration file")?;
+	let branches = Branch::get_all(&config).context("Failed to get all branches")?;
+
+	let mut migrated = 0;
+	for branch in &branches {
+		let content = fs::read_to_string(&branch.path).context("Failed to read branch")?;
+		let new_content = header::migrate(&content, &branch.format, &config.delimiter)
+			.with_context(|| format!("Failed to migrate header of {}", branch.path.display()))?;
+		if let Some(new_content) = new_content {
+			task(format!("Migrating {}", branch.name), || {
+				rewrite(branch, &new_content)
+			})
+			.context("Failed to rewrite branch")?;
+			migrated += 1;
+		}
+	}
+	println!(
+		"{} {} migrated to front matter",
+		migrated,
+		if migrated == 1 { "branch" } else { "branches" }
+	);
+	Ok(())
+}
+
+pub fn rewrite(branch: &Branch, content: &str) -> Result<()> {
+	fs::write(&branch.path, content)?;
+	// Keep the original modification time so outputs aren't reported as stale
+	File::options()
+		.write(true)
+		.open(&branch.path)?
+		.set_modified(branch.mod_time)
+		.context("Failed to restore modification time")
+}
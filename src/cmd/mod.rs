@@ -1,9 +1,20 @@
+pub mod agenda;
+pub mod archive;
 pub mod build;
+pub mod calendar;
 pub mod check;
+pub mod class;
 pub mod commit;
 pub mod completion;
+pub mod config;
+pub mod failures;
+pub mod grades;
+pub mod init;
+pub mod migrate_headers;
+pub mod mv;
 pub mod new;
 pub mod open;
+pub mod relayout;
 pub mod reveal;
 pub mod search;
 pub mod trash;
@@ -0,0 +1,199 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+use std::{env, fs};
+
+use anyhow::{bail, Context, Result};
+use clap::ArgMatches;
+use colorful::Colorful;
+use task_log::task;
+
+use crate::branch::Branch;
+use crate::cmd::{migrate_headers, relayout};
+use crate::conf::{Config, DocumentType};
+use crate::error::{Error, Kind};
+use crate::locations::files;
+use crate::{header, selector};
+
+pub fn run(args: &ArgMatches) -> Result<()> {
+	let config = Config::read().context("Failed to read from configuration file")?;
+	let branch = selector::select(&config, args).context("Failed to select branch")?;
+	let moved = destination(&config, &branch, args)?;
+
+	let moves: Vec<(&PathBuf, &PathBuf)> = [
+		(&branch.path, &moved.path),
+		(&branch.pdf_path, &moved.pdf_path),
+		(&branch.html_path, &moved.html_path),
+		(&branch.docx_path, &moved.docx_path),
+		(&branch.imgs_dir, &moved.imgs_dir),
+		(&branch.build_dir, &moved.build_dir),
+	]
+	.into_iter()
+	.filter(|(from, to)| from != to && from.exists())
+	.collect();
+	if let Some((_, to)) = moves.iter().find(|(_, to)| to.exists()) {
+		return Err(
+			Error::new(Kind::Config, format!("{} already exists", to.display()))
+				.hint("move it out of the way or pick another name")
+				.into(),
+		);
+	}
+
+	task(format!("Moving {}", branch.name), || {
+		apply(&config, &branch, &moved, &moves)
+	})?;
+
+	for (from, to) in moves {
+		println!(
+			"  {} -> {}",
+			from.display(),
+			to.display().to_string().green()
+		);
+	}
+	Ok(())
+}
+
+// Everything done so far is undone when a step fails, so the branch is either fully moved or
+// left where it was
+fn apply(
+	config: &Config,
+	branch: &Branch,
+	moved: &Branch,
+	moves: &[(&PathBuf, &PathBuf)],
+) -> Result<()> {
+	let content = fs::read_to_string(&branch.path).context("Failed to read branch")?;
+	let mut rewritten = false;
+	let mut done = Vec::new();
+	let result = (|| -> Result<()> {
+		// The header wins over the path, so it has to agree with the new location
+		let new_content = header::update(&content, &branch.format, &config.delimiter, |h| {
+			h.class = Some(moved.class.name.clone());
+			h.doc_type = Some(moved.doc_type);
+		})
+		.context("Failed to update header")?;
+		let new_content = branch.move_imgs_refs(&new_content, moved);
+		if new_content != content {
+			migrate_headers::rewrite(branch, &new_content).context("Failed to rewrite branch")?;
+			rewritten = true;
+		}
+
+		let repo = git2::Repository::discover(".").ok();
+		for (from, to) in moves {
+			let git = match &repo {
+				Some(repo) => tracked(repo, from)?,
+				None => false,
+			};
+			if git {
+				git_mv(from, to)
+			} else {
+				relayout::relocate(from, to)
+			}
+			.with_context(|| format!("Failed to move {}", from.display()))?;
+			done.push((from, to, git));
+		}
+		Ok(())
+	})();
+
+	if result.is_err() {
+		for (from, to, git) in done.into_iter().rev() {
+			let _ = if git {
+				git_mv(to, from)
+			} else {
+				relayout::relocate(to, from)
+			};
+		}
+		if rewritten {
+			let _ = migrate_headers::rewrite(branch, &content);
+		}
+	}
+	result
+}
+
+fn destination(config: &Config, branch: &Branch, args: &ArgMatches) -> Result<Branch> {
+	let name = match args.value_of("name") {
+		Some(name) => {
+			let name = name.trim();
+			if name.is_empty() || name.contains(['/', '\\']) {
+				bail!("{} is not a valid branch name", name);
+			}
+			name.to_string()
+		}
+		None => branch.name.clone(),
+	};
+	let class = match args.value_of("to-class") {
+		Some(name) => {
+			config
+				.classes
+				.iter()
+				.find(|c| c.name == name)
+				.cloned()
+				.with_context(|| {
+					Error::new(
+						Kind::Config,
+						format!("{} is not a class in {}", name, files::CONFIG),
+					)
+					.hint("run optic class list to see every class")
+				})?
+		}
+		None => branch.class.clone(),
+	};
+	let doc_type = match args.value_of("to-type") {
+		Some(doc_type) => DocumentType::from_str(doc_type)?,
+		None => branch.doc_type,
+	};
+	if name == branch.name && class.name == branch.class.name && doc_type == branch.doc_type {
+		bail!("{} already has that name, class and type", branch.name);
+	}
+
+	let moved = Branch::new(
+		name,
+		branch.format.clone(),
+		doc_type,
+		class,
+		None,
+		branch.root_template.clone(),
+		branch.creation_time,
+		branch.mod_time,
+		&config.layout,
+	)
+	.context("Failed to lay out branch")?;
+	Ok(match &branch.archived {
+		Some(term) => moved.into_archive(term),
+		None => moved,
+	})
+}
+
+// Anything git knows about (a file or a folder with files in it) is moved with git mv so its
+// history follows it
+fn tracked(repo: &git2::Repository, path: &Path) -> Result<bool> {
+	let workdir = match repo.workdir() {
+		Some(workdir) => workdir,
+		None => return Ok(false),
+	};
+	let relative = env::current_dir()?
+		.canonicalize()?
+		.strip_prefix(workdir.canonicalize()?)?
+		.join(path);
+	Ok(repo
+		.index()?
+		.iter()
+		.any(|entry| Path::new(&*String::from_utf8_lossy(&entry.path)).starts_with(&relative)))
+}
+
+fn git_mv(from: &Path, to: &Path) -> Result<()> {
+	fs::create_dir_all(to.parent().unwrap())?;
+	let output = Command::new("git")
+		.arg("mv")
+		.arg(from)
+		.arg(to)
+		.output()
+		.context("Failed to run git")?;
+	if !output.status.success() {
+		bail!(
+			"git mv failed: {}",
+			String::from_utf8_lossy(&output.stderr).trim()
+		);
+	}
+	relayout::prune(from);
+	Ok(())
+}
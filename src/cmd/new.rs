@@ -1,8 +1,13 @@
+use std::fmt::Display;
 use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::Path;
+use std::str::FromStr;
 use std::time::SystemTime;
 
-use anyhow::Result;
+use anyhow::{anyhow, Context, Result};
 use chrono::Local;
+use clap::ArgMatches;
 use dialoguer::theme::ColorfulTheme;
 use dialoguer::{FuzzySelect, Input, Select};
 use strum::VariantNames;
@@ -10,111 +15,273 @@ use task_log::task;
 
 use crate::branch::Branch;
 use crate::conf::{Class, Config, DocumentType, Format};
+use crate::error::{Error, Kind};
+use crate::header::{self, Status};
+use crate::locations::{files, folders};
 use crate::template::{BranchTemplate, RootTemplate};
 
-pub fn run() {
-	let config = Config::read().expect("Failed to read from config file");
-	let branch = ask(&config).expect("Failed to ask user about branch");
-	let formatted_branch = branch
-		.inject(
-			&config,
-			fs::read_to_string(branch.branch_template.clone().unwrap().path)
-				.expect("Failed to read from branch file"),
-			None,
-		)
-		.expect("Failed to inject variables into branch");
-
-	task("Creating branch", || {
-		fs::create_dir_all(branch.path.parent().unwrap())
-			.expect("Failed to create parent folder for new branch file");
-		fs::create_dir_all(&branch.imgs_dir).expect("Failed to create images directory for branch");
-		fs::write(&branch.path, formatted_branch).expect("Failed to format branch");
-	});
-
-	if config.open_with.is_some() {
-		task(
-			format!(
-				"Opening with {}",
-				config.open_with.as_ref().unwrap().get(0).unwrap()
-			),
-			|| {
-				branch
-					.open(&config)
-					.expect("Failed to open branch in editor");
-			},
-		);
+pub fn run(args: &ArgMatches) -> Result<()> {
+	let config = Config::read().context("Failed to read from config file")?;
+	let branch = ask(&config, args).context("Failed to ask user about branch")?;
+
+	task("Creating branch", || create(&config, &branch)).context("Failed to create branch")?;
+
+	if let Ok(open_with) = config.open_with() {
+		task(format!("Opening with {}", open_with[0]), || {
+			branch.open(&config)
+		})
+		.context("Failed to open branch in editor")?;
 	}
+	Ok(())
 }
 
-fn ask(config: &Config) -> Result<Branch> {
-	let theme = ColorfulTheme::default();
-	let mut branch_templates = BranchTemplate::get_all()?;
-	let mut root_templates = RootTemplate::get_all()?;
-
-	let name: String = Input::with_theme(&theme)
-		.with_prompt("Name")
-		.interact_text()?;
-
-	let format = Format::from_repr(
-		Select::with_theme(&theme)
-			.with_prompt("Format")
-			.items(Format::VARIANTS)
-			.default(0)
-			.interact()?,
-	)
-	.unwrap();
-
-	let doc_type = DocumentType::from_repr(
-		FuzzySelect::with_theme(&theme)
-			.with_prompt("Type")
-			.items(DocumentType::VARIANTS)
-			.default(0)
-			.interact()?,
+pub fn create(config: &Config, branch: &Branch) -> Result<()> {
+	let template = branch
+		.branch_template
+		.as_ref()
+		.context("Branch has no branch template")?;
+	let formatted_branch = header::update(
+		&branch.inject(
+			config,
+			fs::read_to_string(&template.path).context("Failed to read from branch template")?,
+			None,
+		)?,
+		&branch.format,
+		&config.delimiter,
+		|h| {
+			h.class = Some(branch.class.name.clone());
+			h.doc_type = Some(branch.doc_type);
+			h.metadata.due = branch.metadata.due;
+			h.metadata.status = branch.metadata.status;
+		},
 	)
-	.unwrap();
+	.context("Failed to add class and type to branch header")?;
 
-	let mut active_classes = config
-		.classes
-		.iter()
-		.filter(|c| c.active)
-		.cloned()
-		.collect::<Vec<Class>>();
-	let class = active_classes.swap_remove(
-		FuzzySelect::with_theme(&theme)
-			.with_prompt("Class")
-			.items(active_classes.as_slice())
-			.default(0)
-			.interact()?,
-	);
-
-	branch_templates = branch_templates
+	fs::create_dir_all(branch.path.parent().unwrap())
+		.context("Failed to create parent folder for new branch file")?;
+	fs::create_dir_all(&branch.imgs_dir).context("Failed to create images directory for branch")?;
+	fs::write(&branch.path, formatted_branch).context("Failed to write branch")?;
+	Ok(())
+}
+
+fn ask(config: &Config, args: &ArgMatches) -> Result<Branch> {
+	let theme = ColorfulTheme::default();
+	let branch_templates = BranchTemplate::get_all()?;
+	let root_templates = RootTemplate::get_all()?;
+
+	let name: String = match args.value_of("name") {
+		Some(name) => name.to_string(),
+		None => {
+			Input::with_theme(&theme)
+				.with_prompt("Name")
+				.interact_text()?
+		}
+	};
+
+	let format = match args.value_of("format") {
+		Some(format) => {
+			Format::from_str(format).map_err(|_| {
+				anyhow!(
+					"{} is not a valid format. Options are: {}",
+					format,
+					Format::VARIANTS.join(", ")
+				)
+			})?
+		}
+		None => {
+			Format::from_repr(
+				Select::with_theme(&theme)
+					.with_prompt("Format")
+					.items(Format::VARIANTS)
+					.default(0)
+					.interact()?,
+			)
+			.unwrap()
+		}
+	};
+
+	let doc_type = match args.value_of("type") {
+		Some(doc_type) => {
+			DocumentType::from_str(doc_type).map_err(|_| {
+				anyhow!(
+					"{} is not a valid document type. Options are: {}",
+					doc_type,
+					DocumentType::VARIANTS.join(", ")
+				)
+			})?
+		}
+		None => {
+			DocumentType::from_repr(
+				FuzzySelect::with_theme(&theme)
+					.with_prompt("Type")
+					.items(DocumentType::VARIANTS)
+					.default(0)
+					.interact()?,
+			)
+			.unwrap()
+		}
+	};
+
+	let class = match args.value_of("class") {
+		Some(class_name) => {
+			config
+				.classes
+				.iter()
+				.find(|c| c.name == class_name)
+				.cloned()
+				.with_context(|| {
+					format!(
+						"{} is not a class in {}. Options are: {}",
+						class_name,
+						files::CONFIG,
+						config
+							.classes
+							.iter()
+							.map(|c| c.name.as_str())
+							.collect::<Vec<&str>>()
+							.join(", ")
+					)
+				})?
+		}
+		None => {
+			let mut active_classes = config
+				.classes
+				.iter()
+				.filter(|c| c.is_active(&config.terms, Local::now().date_naive()))
+				.cloned()
+				.collect::<Vec<Class>>();
+			if active_classes.is_empty() {
+				return Err(Error::new(Kind::Config, "No active classes to pick from")
+					.hint(format!(
+						"add a class to {} or mark one as `active = true`",
+						files::CONFIG
+					))
+					.into());
+			}
+			active_classes.swap_remove(
+				FuzzySelect::with_theme(&theme)
+					.with_prompt("Class")
+					.items(active_classes.as_slice())
+					.default(0)
+					.interact()?,
+			)
+		}
+	};
+
+	let mut branch_templates: Vec<BranchTemplate> = branch_templates
 		.into_iter()
 		.filter(|b| b.format == format)
 		.collect();
-	let branch_template = branch_templates.swap_remove(
-		FuzzySelect::with_theme(&theme)
-			.with_prompt("Branch Template")
-			.items(branch_templates.as_slice())
-			.default(0)
-			.interact()?,
-	);
-
-	let root_template = root_templates.swap_remove(
-		FuzzySelect::with_theme(&theme)
-			.with_prompt("Root Template")
-			.items(root_templates.as_slice())
-			.default(0)
-			.interact()?,
-	);
-
-	Branch::new(
+	let branch_template = match args.value_of("branch-template") {
+		Some(template_name) => {
+			let position = branch_templates
+				.iter()
+				.position(|t| t.name == template_name)
+				.with_context(|| {
+					format!(
+						"{} is not a {} template in {}. Options are: {}",
+						template_name,
+						format,
+						Path::new(folders::TEMPLATES)
+							.join(folders::BRANCH_TEMPLATES)
+							.display(),
+						template_names(&branch_templates)
+					)
+				})?;
+			branch_templates.swap_remove(position)
+		}
+		None => {
+			branch_templates.swap_remove(
+				FuzzySelect::with_theme(&theme)
+					.with_prompt("Branch Template")
+					.items(branch_templates.as_slice())
+					.default(0)
+					.interact()?,
+			)
+		}
+	};
+
+	let mut root_templates: Vec<RootTemplate> = root_templates
+		.into_iter()
+		.filter(|r| {
+			Format::from_path(Path::new(&r.name)).unwrap_or_default()
+				== format.plugin().root_format()
+		})
+		.collect();
+	let root_template = match args.value_of("root-template") {
+		Some(template_name) => {
+			let position = root_templates
+				.iter()
+				.position(|t| t.name == template_name)
+				.with_context(|| {
+					format!(
+						"{} is not a template in {}. Options are: {}",
+						template_name,
+						Path::new(folders::TEMPLATES)
+							.join(folders::ROOT_TEMPLATES)
+							.display(),
+						template_names(&root_templates)
+					)
+				})?;
+			root_templates.swap_remove(position)
+		}
+		None => {
+			root_templates.swap_remove(
+				FuzzySelect::with_theme(&theme)
+					.with_prompt("Root Template")
+					.items(root_templates.as_slice())
+					.default(0)
+					.interact()?,
+			)
+		}
+	};
+
+	let today = Local::now().date_naive();
+	// Due is optional, so it's only asked for alongside the other prompts. When every flag is
+	// given, a missing --due means no due date
+	let prompted = [
+		"name",
+		"format",
+		"type",
+		"class",
+		"branch-template",
+		"root-template",
+	]
+	.iter()
+	.any(|arg| !args.is_present(arg));
+	let due = match args.value_of("due") {
+		Some(due) => due.to_string(),
+		None if prompted && io::stdin().is_terminal() => {
+			Input::with_theme(&theme)
+				.with_prompt("Due (e.g. friday, +3d, 2022-10-07)")
+				.allow_empty(true)
+				.interact_text()?
+		}
+		None => String::new(),
+	};
+
+	let mut branch = Branch::new(
 		name,
 		format,
 		doc_type,
 		class,
 		Some(branch_template),
 		root_template,
-		Local::now().date(),
+		today,
 		SystemTime::now(),
-	)
+		&config.layout,
+	)?;
+	if !due.trim().is_empty() {
+		branch.metadata.due = Some(header::parse_due(&due, today)?);
+		branch.metadata.status = Some(Status::Todo);
+	}
+	Ok(branch)
+}
+
+fn template_names<T: Display>(templates: &[T]) -> String {
+	templates
+		.iter()
+		.map(|t| t.to_string())
+		.collect::<Vec<String>>()
+		.join(", ")
 }
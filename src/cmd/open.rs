@@ -1,17 +1,14 @@
+use anyhow::{Context, Result};
+use clap::ArgMatches;
 use task_log::task;
 
-use crate::branch::Branch;
 use crate::conf::Config;
+use crate::selector;
 
-pub fn run() {
-	let config = Config::read().expect("Failed to read from configuration file");
-	let branches = Branch::get_all(&config).expect("Failed to get all branches");
+pub fn run(args: &ArgMatches) -> Result<()> {
+	let config = Config::read().context("Failed to read from configuration file")?;
+	let branch = selector::select(&config, args).context("Failed to select branch")?;
 
-	task("Opening with editor", || {
-		branches
-			.get(0)
-			.unwrap()
-			.open(&config)
-			.expect("Failed to open branch with editor");
-	});
+	task("Opening with editor", || branch.open(&config))
+		.context("Failed to open branch with editor")
 }
@@ -0,0 +1,188 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::{bail, Context, Result};
+use clap::ArgMatches;
+use colorful::Colorful;
+use task_log::task;
+use walkdir::WalkDir;
+
+use crate::branch::{self, Branch};
+use crate::cmd::migrate_headers;
+use crate::conf::Config;
+use crate::error::{Error, Kind};
+use crate::header;
+use crate::layout::Layout;
+
+pub fn run(args: &ArgMatches) -> Result<()> {
+	let config = Config::read().context("Failed to read from configuration file")?;
+	let from = args
+		.value_of("from")
+		.map(Layout::from_str)
+		.transpose()
+		.context("Failed to parse previous layout")?
+		.unwrap_or_default();
+	let dry_run = args.is_present("dry-run");
+	let branches = Branch::get_all_with_archive(&config).context("Failed to get all branches")?;
+
+	let mut planned: Vec<&Branch> = Vec::new();
+	let mut moves: Vec<Vec<(PathBuf, PathBuf)>> = Vec::new();
+	for branch in &branches {
+		let old = branch
+			.relocated(&branch.class, &from)
+			.context("Failed to lay out branch")?;
+		let new = branch
+			.relocated(&branch.class, &config.layout)
+			.context("Failed to lay out branch")?;
+		let branch_moves: Vec<(PathBuf, PathBuf)> = [
+			(&branch.path, new.path),
+			(&old.pdf_path, new.pdf_path),
+			(&old.html_path, new.html_path),
+			(&old.docx_path, new.docx_path),
+			(&old.imgs_dir, new.imgs_dir),
+			(&old.build_dir, new.build_dir),
+		]
+		.into_iter()
+		.filter(|(from, to)| from != &to && from.exists())
+		.map(|(from, to)| (from.clone(), to))
+		.collect();
+		if !branch_moves.is_empty() {
+			planned.push(branch);
+			moves.push(branch_moves);
+		}
+	}
+	// Every move is checked before anything is touched, so a clash can't leave the project half
+	// moved
+	branch::merge_moves(&mut moves)?;
+	if let Some((_, to)) = moves.iter().flatten().find(|(_, to)| to.exists()) {
+		return Err(
+			Error::new(Kind::Config, format!("{} already exists", to.display()))
+				.hint("move it out of the way or pick another layout")
+				.into(),
+		);
+	}
+	let plan: Vec<(&Branch, Vec<(PathBuf, PathBuf)>)> = planned.into_iter().zip(moves).collect();
+
+	if dry_run {
+		for (from, to) in plan.iter().flat_map(|(_, moves)| moves) {
+			println!(
+				"  {} -> {}",
+				from.display(),
+				to.display().to_string().green()
+			);
+		}
+	} else {
+		apply(&config, &plan)?;
+	}
+	println!(
+		"{} {} {}",
+		plan.len(),
+		if plan.len() == 1 {
+			"branch"
+		} else {
+			"branches"
+		},
+		if dry_run { "would be moved" } else { "moved" }
+	);
+	Ok(())
+}
+
+// Everything done so far is undone when a step fails, so the project is either fully in the
+// new layout or still in the old one
+fn apply(config: &Config, plan: &[(&Branch, Vec<(PathBuf, PathBuf)>)]) -> Result<()> {
+	let mut rewritten = Vec::new();
+	let mut moved: Vec<(&PathBuf, &PathBuf)> = Vec::new();
+	let mut copied = Vec::new();
+	let result = (|| -> Result<()> {
+		for (branch, moves) in plan {
+			task(format!("Moving {}", branch.name), || -> Result<()> {
+				if let Some(original) = record_fields(config, branch)
+					.context("Failed to add class and type to header")?
+				{
+					rewritten.push((*branch, original));
+				}
+				for (from, to) in moves {
+					// A folder shared under the old layout goes to every branch that used it
+					match moved.iter().find(|(m, _)| *m == from) {
+						Some((_, first)) => {
+							copy(first, to).context("Failed to copy shared folder")?;
+							copied.push(to);
+						}
+						None => {
+							relocate(from, to).context("Failed to move file")?;
+							moved.push((from, to));
+						}
+					}
+				}
+				Ok(())
+			})?;
+		}
+		Ok(())
+	})();
+
+	if result.is_err() {
+		for to in copied {
+			let _ = fs::remove_dir_all(to).or_else(|_| fs::remove_file(to));
+			prune(to);
+		}
+		for (from, to) in moved.into_iter().rev() {
+			let _ = relocate(to, from);
+		}
+		for (branch, original) in rewritten {
+			let _ = migrate_headers::rewrite(branch, &original);
+		}
+	}
+	result
+}
+
+// Later layouts might not be able to recover these from the path. Returns the original content
+// when the header had to change
+fn record_fields(config: &Config, branch: &Branch) -> Result<Option<String>> {
+	let content = fs::read_to_string(&branch.path)?;
+	let new_content = header::update(&content, &branch.format, &config.delimiter, |h| {
+		h.class = Some(branch.class.name.clone());
+		h.doc_type = Some(branch.doc_type);
+	})?;
+	if new_content == content {
+		return Ok(None);
+	}
+	migrate_headers::rewrite(branch, &new_content)?;
+	Ok(Some(content))
+}
+
+pub fn relocate(from: &Path, to: &Path) -> Result<()> {
+	if to.exists() {
+		bail!("{} already exists", to.display());
+	}
+	fs::create_dir_all(to.parent().unwrap())?;
+	fs::rename(from, to)?;
+	prune(from);
+	Ok(())
+}
+
+fn copy(from: &Path, to: &Path) -> Result<()> {
+	for entry in WalkDir::new(from) {
+		let entry = entry?;
+		let target = to.join(entry.path().strip_prefix(from)?);
+		if entry.file_type().is_dir() {
+			fs::create_dir_all(&target)?;
+		} else {
+			fs::create_dir_all(target.parent().unwrap())?;
+			fs::copy(entry.path(), &target)?;
+		}
+	}
+	Ok(())
+}
+
+// Removes the folders a moved file leaves empty
+pub fn prune(from: &Path) {
+	let mut parent = from.parent();
+	while let Some(dir) = parent {
+		let top_level = dir.parent().is_none_or(|p| p.as_os_str().is_empty());
+		if top_level || fs::remove_dir(dir).is_err() {
+			break;
+		}
+		parent = dir.parent();
+	}
+}
@@ -1,16 +1,16 @@
+use anyhow::{Context, Result};
+use clap::ArgMatches;
 use task_log::task;
 
-use crate::branch::Branch;
 use crate::conf::Config;
+use crate::selector;
 
-pub fn run() {
-	let config = Config::read().expect("Failed to read configuration file");
-	let branches = Branch::get_all(&config).expect("Failed to get all branches");
-	let branch = branches.get(0).unwrap();
+pub fn run(args: &ArgMatches) -> Result<()> {
+	let config = Config::read().context("Failed to read configuration file")?;
+	let branch = selector::select(&config, args).context("Failed to select branch")?;
 
 	task(format!("Revealing {}", branch.name), || {
-		branch
-			.reveal(&config, true)
-			.expect("Failed to reveal branch");
+		branch.reveal(&config, true)
 	})
+	.context("Failed to reveal branch")
 }
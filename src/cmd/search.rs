@@ -1,51 +1,65 @@
-use anyhow::Result;
+use anyhow::{anyhow, bail, Context, Result};
 use copypasta::{ClipboardContext, ClipboardProvider};
 use dialoguer::theme::ColorfulTheme;
-use dialoguer::{FuzzySelect, Select};
+use dialoguer::Select;
 use strum::VariantNames;
 use strum_macros::{Display, EnumVariantNames, FromRepr};
 use task_log::task;
 
 use crate::branch::Branch;
-use crate::conf::Config;
+use crate::conf::{Config, Target};
+use crate::selector;
 
-pub fn run() {
-	let config = Config::read().expect("Failed to read from configuration file");
-	let branches = Branch::get_all(&config).expect("Failed to get branches");
-	let (branch, action) = ask(&branches).expect("Failed to ask user for branch");
+pub fn run() -> Result<()> {
+	let config = Config::read().context("Failed to read from configuration file")?;
+	let branches = Branch::get_all_with_archive(&config).context("Failed to get branches")?;
+	if branches.is_empty() {
+		bail!("No branches to search. Create one with optic new");
+	}
+	let (branch, action) = ask(&branches).context("Failed to ask user for branch")?;
 	println!();
 	match action {
 		Action::Build => {
 			task(format!("Building {}", branch.name), || {
-				branch
-					.build(&config, &config.latexmk)
-					.expect("Failed to build PDF")
+				branch.build(&config, None)
 			})
+			.context("Failed to build PDF")
 		}
-		Action::Open => {
-			task(format!("Opening {}", branch.name), || {
-				branch.open(&config).expect("Failed to open branch")
+		Action::ExportDocx => {
+			task(format!("Exporting {} to DOCX", branch.name), || {
+				branch.build_target(&config, &Target::Docx, None)
 			})
+			.context("Failed to export DOCX")
+		}
+		Action::Open => {
+			task(format!("Opening {}", branch.name), || branch.open(&config))
+				.context("Failed to open branch")
 		}
 		Action::Reveal => {
 			task(format!("Revealing {}", branch.name), || {
-				branch.reveal(&config, true).expect("Failed to reveal PDF")
+				branch.reveal(&config, true)
 			})
+			.context("Failed to reveal PDF")
 		}
 		Action::Path => {
 			println!("{}", branch.path.display());
 			ClipboardContext::new()
-				.expect("Failed to setup clipboard context")
-				.set_contents(branch.path.to_str().unwrap().to_string())
-				.expect("Failed to set clipboard context");
+				.map_err(|e| anyhow!("{}", e))
+				.context("Failed to setup clipboard context")?
+				.set_contents(branch.path.display().to_string())
+				.map_err(|e| anyhow!("{}", e))
+				.context("Failed to set clipboard context")?;
 			println!("Copied to clipboard");
+			Ok(())
 		}
-	};
+	}
 }
 
 #[derive(Display, FromRepr, EnumVariantNames, Debug)]
 enum Action {
 	Build,
+	#[strum(serialize = "Export DOCX")]
+	ExportDocx,
 	Open,
 	Reveal,
 	Path,
@@ -53,14 +67,7 @@ enum Action {
 
 fn ask(branches: &[Branch]) -> Result<(&Branch, Action)> {
 	let theme = ColorfulTheme::default();
-	let branch = branches
-		.get(
-			FuzzySelect::with_theme(&theme)
-				.with_prompt("Branch")
-				.items(branches)
-				.interact()?,
-		)
-		.unwrap();
+	let branch = &branches[selector::pick(branches)?];
 
 	let action = Action::from_repr(
 		Select::with_theme(&theme)
@@ -1,19 +1,26 @@
+use anyhow::{Context, Result};
+use clap::ArgMatches;
 use task_log::task;
 
-use crate::branch::Branch;
 use crate::conf::Config;
+use crate::selector;
 
-pub fn run() {
-	let config = Config::read().expect("Failed to read from configuration file");
-	let branches = Branch::get_all(&config).expect("Failed to get all branches");
-	let branch = branches.get(0).unwrap();
+pub fn run(args: &ArgMatches) -> Result<()> {
+	let config = Config::read().context("Failed to read from configuration file")?;
+	let branch = selector::select(&config, args).context("Failed to select branch")?;
 
 	task(format!("Moving {} to trash", branch.name), || {
 		trash::delete_all(
-			[&branch.path, &branch.pdf_path, &branch.imgs_dir]
-				.into_iter()
-				.filter(|x| x.exists()),
+			[
+				&branch.path,
+				&branch.pdf_path,
+				&branch.html_path,
+				&branch.docx_path,
+				&branch.imgs_dir,
+			]
+			.into_iter()
+			.filter(|x| x.exists()),
 		)
-		.expect("Failed to move branch file, PDF, or images directory to trash");
 	})
+	.context("Failed to move branch file, outputs, or images directory to trash")
 }
@@ -1,6 +1,7 @@
 use std::sync::mpsc::channel;
 use std::time::Duration;
 
+use anyhow::{Context, Result};
 use chrono::Local;
 use chrono_humanize::{Accuracy, HumanTime, Tense};
 use clap::ArgMatches;
@@ -8,43 +9,38 @@ use colorful::Colorful;
 use notify::{DebouncedEvent, RecommendedWatcher, Watcher};
 use task_log::task;
 
-use crate::branch::Branch;
+use crate::cmd::build;
 use crate::conf::Config;
+use crate::{diagnostic, selector};
 
-pub fn run(args: &ArgMatches) {
-	let config = Config::read().expect("Failed to read from configuration file");
-	let branches = Branch::get_all(&config).expect("Failed to get all branches");
-	let branch = branches.get(0).unwrap();
+pub fn run(args: &ArgMatches) -> Result<()> {
+	let config = Config::read().context("Failed to read from configuration file")?;
+	let branch = selector::select(&config, args).context("Failed to select branch")?;
+	let engine = build::engine(args).context("Failed to parse engine")?;
+	let view_with = config.view_with()?;
 
 	let (tx, rx) = channel();
 	let mut watcher: RecommendedWatcher =
-		Watcher::new(tx, Duration::from_millis(20)).expect("Failed to setup watcher");
+		Watcher::new(tx, Duration::from_millis(20)).context("Failed to setup watcher")?;
 	watcher
 		.watch(&branch.path, notify::RecursiveMode::NonRecursive)
-		.expect("Failed to watch recent branch file");
+		.context("Failed to watch branch file")?;
 
 	watcher
 		.watch(
 			&branch.root_template.path,
 			notify::RecursiveMode::NonRecursive,
 		)
-		.expect("Failed to watch branch's root template file");
+		.context("Failed to watch branch's root template file")?;
 
 	task(
-		format!(
-			"Opening \"{}\" with {}",
-			branch.name,
-			config.view_with.as_ref().unwrap().get(0).unwrap()
-		),
-		|| {
-			branch
-				.view(&config, false, true)
-				.expect("Failed to open branch with viewer");
-		},
-	);
+		format!("Opening \"{}\" with {}", branch.name, view_with[0]),
+		|| branch.view(&config, false, true),
+	)
+	.context("Failed to open branch with viewer")?;
 
 	loop {
-		let event = rx.recv().expect("Failed to receive event");
+		let event = rx.recv().context("Failed to receive event")?;
 		match event {
 			DebouncedEvent::Write(_) => {
 				let start = Local::now();
@@ -54,9 +50,10 @@ pub fn run(args: &ArgMatches) {
 						.bg_yellow()
 						.black()
 				);
-				let result = branch.build(&config, &(config.latexmk || args.is_present("latexmk")));
-				if result.is_err() {
+				let result = branch.build(&config, engine.as_ref());
+				if let Err(err) = result {
 					println!("   {}", "BUILD FAILED".red());
+					diagnostic::report(&branch, &err, "   ");
 				} else {
 					println!(
 						"   {}",
@@ -72,7 +69,7 @@ pub fn run(args: &ArgMatches) {
 			}
 			DebouncedEvent::NoticeRemove(path) | DebouncedEvent::Remove(path) => {
 				println!("\n{} has been deleted. Stopping watch", path.display());
-				return;
+				return Ok(());
 			}
 			_ => (),
 		}
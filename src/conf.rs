@@ -1,13 +1,25 @@
-use std::path::Path;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
 use std::{fmt, fs};
 
-use anyhow::Result;
-use serde::Deserialize;
-use strum_macros::{Display, EnumString, EnumVariantNames, FromRepr};
+use anyhow::{bail, Context, Result};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use strum_macros::{Display, EnumIter, EnumString, EnumVariantNames, FromRepr};
+use toml::value::{Table, Value};
 
+use crate::error::{Error, Kind};
+use crate::format::{self, Plugin};
+use crate::header::date;
+use crate::layout::Layout;
 use crate::locations;
 
-#[derive(Deserialize, Debug, PartialEq)]
+const GLOBAL_KEYS: [&str; 4] = ["author", "open_with", "view_with", "engine"];
+const GLOBAL_CONFIG_HINT: &str = "~/.config/optic/config.toml";
+
+#[derive(Deserialize, Serialize, Debug, PartialEq)]
 pub struct Config {
 	pub author: String,
 	#[serde(default = "defaults::config_delimiter")]
@@ -15,27 +27,109 @@ pub struct Config {
 	pub open_with: Option<Vec<String>>,
 	#[serde(default = "defaults::config_default_format")]
 	pub default_format: Format,
+	#[serde(default)]
 	pub classes: Vec<Class>,
 	pub view_with: Option<Vec<String>>,
-	#[serde(default = "defaults::latexmk")]
+	// Replaced by engine, but still honored so older configs keep building with latexmk
+	#[serde(default)]
 	pub latexmk: bool,
+	pub engine: Option<Engine>,
+	#[serde(default = "defaults::max_reruns")]
+	pub max_reruns: u8,
+	pub bibliography: Option<Bibliography>,
+	#[serde(default)]
+	pub markdown_converter: MarkdownConverter,
+	#[serde(default = "defaults::outputs")]
+	pub outputs: Vec<Target>,
+	#[serde(default)]
+	pub type_outputs: HashMap<String, Vec<Target>>,
+	#[serde(default = "defaults::grade_scale")]
+	pub grade_scale: Vec<Grade>,
+	#[serde(default)]
+	pub layout: Layout,
+	#[serde(default)]
+	pub terms: Vec<Term>,
 }
 
-#[derive(Deserialize, Debug, PartialEq, Clone)]
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone, Default)]
 pub struct Class {
 	pub name: String,
+	// Names the class had before, which branch headers written back then still use
+	#[serde(default)]
+	pub renamed_from: Vec<String>,
 	pub teacher: String,
-	#[serde(default = "defaults::class_active")]
-	pub active: bool,
+	pub active: Option<bool>,
+	#[serde(default)]
+	pub terms: Vec<String>,
+	pub engine: Option<Engine>,
+	pub bibliography: Option<Bibliography>,
+	pub outputs: Option<Vec<Target>>,
+	pub reference_doc: Option<PathBuf>,
+	pub format: Option<Format>,
+	pub branch_template: Option<String>,
+	pub root_template: Option<String>,
+	#[serde(default)]
+	pub weights: HashMap<String, f64>,
+	pub credits: Option<f64>,
+}
+
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone, Default)]
+pub struct Bibliography {
+	#[serde(default)]
+	pub files: Vec<PathBuf>,
+	pub csl: Option<PathBuf>,
+}
+
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+pub struct Term {
+	pub name: String,
+	#[serde(deserialize_with = "date::required")]
+	pub start: NaiveDate,
+	#[serde(deserialize_with = "date::required")]
+	pub end: NaiveDate,
+}
+
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+pub struct Grade {
+	pub letter: String,
+	pub min: f64,
+	pub points: f64,
 }
 
-#[derive(PartialEq, Debug, Display, Deserialize, EnumVariantNames, EnumString, Clone, FromRepr)]
+#[derive(
+	PartialEq,
+	Debug,
+	Display,
+	Deserialize,
+	Serialize,
+	EnumVariantNames,
+	EnumString,
+	EnumIter,
+	Clone,
+	FromRepr,
+	Default,
+)]
 pub enum Format {
+	#[default]
 	LaTeX,
 	Markdown,
+	Typst,
+	AsciiDoc,
+	ReStructuredText,
 }
 
-#[derive(PartialEq, Debug, Display, Deserialize, EnumVariantNames, EnumString, FromRepr)]
+#[derive(
+	PartialEq,
+	Debug,
+	Display,
+	Deserialize,
+	Serialize,
+	EnumVariantNames,
+	EnumString,
+	FromRepr,
+	Clone,
+	Copy,
+)]
 pub enum DocumentType {
 	Worksheet,
 	Note,
@@ -45,43 +139,455 @@ pub enum DocumentType {
 	Other,
 }
 
+#[derive(
+	PartialEq,
+	Eq,
+	Hash,
+	Debug,
+	Display,
+	Deserialize,
+	Serialize,
+	EnumVariantNames,
+	EnumString,
+	Clone,
+	Copy,
+)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum Target {
+	Pdf,
+	Html,
+	Docx,
+}
+
+#[derive(PartialEq, Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum MarkdownConverter {
+	#[default]
+	Pandoc,
+	Native,
+}
+
+#[derive(PartialEq, Debug, Deserialize, Serialize, Clone)]
+#[serde(try_from = "String", into = "String")]
+pub enum Engine {
+	PdfLaTeX,
+	XeLaTeX,
+	LuaLaTeX,
+	Latexmk(Box<Engine>),
+	Tectonic,
+	Command(Vec<String>),
+}
+
 mod defaults {
-	use super::Format;
+	use super::{Format, Grade, Target};
 
-	pub fn latexmk() -> bool { false }
+	pub fn max_reruns() -> u8 { 3 }
+	pub fn outputs() -> Vec<Target> { vec![Target::Pdf] }
 
 	pub fn config_delimiter() -> String { String::from(">") }
 	pub fn config_default_format() -> Format { Format::Markdown }
 
-	pub fn class_active() -> bool { true }
+	pub fn grade_scale() -> Vec<Grade> {
+		[
+			("A", 93.0, 4.0),
+			("A-", 90.0, 3.7),
+			("B+", 87.0, 3.3),
+			("B", 83.0, 3.0),
+			("B-", 80.0, 2.7),
+			("C+", 77.0, 2.3),
+			("C", 73.0, 2.0),
+			("C-", 70.0, 1.7),
+			("D+", 67.0, 1.3),
+			("D", 63.0, 1.0),
+			("D-", 60.0, 0.7),
+			("F", 0.0, 0.0),
+		]
+		.into_iter()
+		.map(|(letter, min, points)| {
+			Grade {
+				letter: String::from(letter),
+				min,
+				points,
+			}
+		})
+		.collect()
+	}
 }
 
 impl Config {
-	pub fn read() -> Result<Config> {
-		let content = fs::read_to_string(locations::files::CONFIG)?;
-		Ok(toml::from_str::<Config>(&content)?)
+	pub fn read() -> Result<Config> { Layers::read()?.config() }
+
+	pub fn view_with(&self) -> Result<&Vec<String>> {
+		self.view_with.as_ref().filter(|v| !v.is_empty()).context(
+			Error::new(Kind::Config, "No PDF viewer configured").hint(format!(
+				"add `view_with = [...]` to {} or {}",
+				locations::files::CONFIG,
+				GLOBAL_CONFIG_HINT
+			)),
+		)
+	}
+
+	pub fn open_with(&self) -> Result<&Vec<String>> {
+		self.open_with.as_ref().filter(|v| !v.is_empty()).context(
+			Error::new(Kind::Config, "No editor configured").hint(format!(
+				"add `open_with = [...]` to {} or {}",
+				locations::files::CONFIG,
+				GLOBAL_CONFIG_HINT
+			)),
+		)
+	}
+
+	pub fn term(&self, name: &str) -> Result<&Term> {
+		self.terms.iter().find(|t| t.name == name).with_context(|| {
+			format!(
+				"{} is not a term in {}. Options are: {}",
+				name,
+				locations::files::CONFIG,
+				self.terms
+					.iter()
+					.map(|t| t.name.as_str())
+					.collect::<Vec<&str>>()
+					.join(", ")
+			)
+		})
+	}
+
+	pub fn engine(&self) -> Engine {
+		match &self.engine {
+			Some(engine) => engine.clone(),
+			None if self.latexmk => Engine::Latexmk(Box::new(Engine::PdfLaTeX)),
+			None => Engine::PdfLaTeX,
+		}
 	}
 }
 
-impl Format {
-	pub fn extension(&self) -> &'static str {
-		match *self {
-			Format::LaTeX => ".tex",
-			Format::Markdown => ".md",
+impl Engine {
+	pub fn program(&self) -> &str {
+		match self {
+			Engine::PdfLaTeX => "pdflatex",
+			Engine::XeLaTeX => "xelatex",
+			Engine::LuaLaTeX => "lualatex",
+			Engine::Latexmk(_) => "latexmk",
+			Engine::Tectonic => "tectonic",
+			Engine::Command(args) => args.first().map_or("", |a| a.as_str()),
+		}
+	}
+
+	pub fn multi_pass(&self) -> bool {
+		matches!(self, Engine::PdfLaTeX | Engine::XeLaTeX | Engine::LuaLaTeX)
+	}
+
+	pub fn command(&self, input: &str, outdir: &str) -> Command {
+		let mut cmd = Command::new(self.program());
+		match self {
+			Engine::PdfLaTeX | Engine::XeLaTeX | Engine::LuaLaTeX => {
+				cmd.arg("-interaction=nonstopmode").arg(input);
+			}
+			Engine::Latexmk(backend) => {
+				cmd.arg("-interaction=nonstopmode")
+					.arg(match **backend {
+						Engine::XeLaTeX => "-pdfxe",
+						Engine::LuaLaTeX => "-pdflua",
+						_ => "-pdf",
+					})
+					.arg(input);
+			}
+			Engine::Tectonic => {
+				cmd.arg("--outdir").arg(outdir).arg(input);
+			}
+			Engine::Command(args) => {
+				cmd.args(
+					args.iter()
+						.skip(1)
+						.map(|a| a.replace("{input}", input).replace("{outdir}", outdir)),
+				);
+			}
+		}
+		cmd
+	}
+
+	pub fn from_magic_comment(content: &str) -> Option<Result<Self>> {
+		content.lines().find_map(|line| {
+			let comment = line.trim().strip_prefix('%')?.trim();
+			let (key, value) = comment.split_once('=')?;
+			if key.trim().eq_ignore_ascii_case("!TEX program") {
+				Some(Engine::from_str(value.trim()))
+			} else {
+				None
+			}
+		})
+	}
+}
+
+impl FromStr for Engine {
+	type Err = anyhow::Error;
+
+	fn from_str(s: &str) -> Result<Self> {
+		if s.contains("{input}") {
+			return Ok(Engine::Command(split_command(s)?));
 		}
+		Ok(match s.to_lowercase().as_str() {
+			"pdflatex" => Engine::PdfLaTeX,
+			"xelatex" => Engine::XeLaTeX,
+			"lualatex" => Engine::LuaLaTeX,
+			"tectonic" => Engine::Tectonic,
+			"latexmk" => Engine::Latexmk(Box::new(Engine::PdfLaTeX)),
+			other => {
+				match other.strip_prefix("latexmk:").map(Engine::from_str) {
+					Some(Ok(backend @ (Engine::PdfLaTeX | Engine::XeLaTeX | Engine::LuaLaTeX))) => {
+						Engine::Latexmk(Box::new(backend))
+					}
+					_ => {
+						bail!(
+							"{} is not a valid engine. Options are: pdflatex, xelatex, lualatex, \
+							 latexmk, latexmk:<pdflatex|xelatex|lualatex>, tectonic, or a command \
+							 containing {{input}}",
+							s
+						)
+					}
+				}
+			}
+		})
 	}
+}
+
+// Splits a command like a shell would, so arguments with spaces can be quoted
+fn split_command(s: &str) -> Result<Vec<String>> {
+	let mut args = Vec::new();
+	let mut current: Option<String> = None;
+	let mut chars = s.chars();
+	while let Some(c) = chars.next() {
+		match c {
+			'\'' => {
+				let arg = current.get_or_insert_with(String::new);
+				loop {
+					match chars.next() {
+						Some('\'') => break,
+						Some(c) => arg.push(c),
+						None => bail!("Unclosed ' in engine command {}", s),
+					}
+				}
+			}
+			'"' => {
+				let arg = current.get_or_insert_with(String::new);
+				loop {
+					match chars.next() {
+						Some('"') => break,
+						Some('\\') => arg.extend(chars.next()),
+						Some(c) => arg.push(c),
+						None => bail!("Unclosed \" in engine command {}", s),
+					}
+				}
+			}
+			'\\' => current.get_or_insert_with(String::new).extend(chars.next()),
+			c if c.is_whitespace() => args.extend(current.take()),
+			c => current.get_or_insert_with(String::new).push(c),
+		}
+	}
+	args.extend(current);
+	Ok(args)
+}
+
+impl TryFrom<String> for Engine {
+	type Error = anyhow::Error;
+
+	fn try_from(value: String) -> Result<Self> { Engine::from_str(&value) }
+}
+
+impl From<Engine> for String {
+	fn from(engine: Engine) -> Self { engine.to_string() }
+}
+
+impl fmt::Display for Engine {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Engine::Latexmk(backend) => write!(f, "latexmk:{}", backend),
+			Engine::Command(args) => {
+				let quoted: Vec<String> = args
+					.iter()
+					.map(|a| {
+						if a.is_empty()
+							|| a.contains(|c: char| c.is_whitespace() || "'\"\\".contains(c))
+						{
+							format!("'{}'", a.replace('\'', "'\\''"))
+						} else {
+							a.clone()
+						}
+					})
+					.collect();
+				write!(f, "{}", quoted.join(" "))
+			}
+			_ => write!(f, "{}", self.program()),
+		}
+	}
+}
+
+impl Format {
+	pub fn plugin(&self) -> &'static dyn Plugin {
+		*format::PLUGINS
+			.iter()
+			.find(|p| p.format() == *self)
+			.expect("Every format has a plugin")
+	}
+
+	pub fn extension(&self) -> &'static str { self.plugin().extension() }
 
 	pub fn from_path(path: &Path) -> Option<Self> {
-		match path.extension().unwrap_or_default().to_str().unwrap() {
-			"tex" => Some(Format::LaTeX),
-			"md" => Some(Format::Markdown),
+		let extension = path.extension()?.to_str()?;
+		format::PLUGINS
+			.iter()
+			.find(|p| p.extension().strip_prefix('.') == Some(extension))
+			.map(|p| p.format())
+	}
+}
+
+impl Class {
+	pub fn is_active(&self, terms: &[Term], today: NaiveDate) -> bool {
+		match self.active {
+			Some(active) => active,
+			None if self.terms.is_empty() => true,
+			None => {
+				terms
+					.iter()
+					.any(|t| self.terms.contains(&t.name) && t.contains(today))
+			}
+		}
+	}
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Source {
+	Project,
+	Global(PathBuf),
+	Default,
+}
+
+// Values in optic.toml take precedence over the global config, which takes precedence over the
+// built-in defaults
+#[derive(Debug, PartialEq)]
+pub struct Layers {
+	pub content: String,
+	pub project: Table,
+	pub global: Option<(PathBuf, Table)>,
+}
+
+impl Layers {
+	pub fn read() -> Result<Self> {
+		locations::enter_root()?;
+		let content = read_layer(Path::new(locations::files::CONFIG))?;
+		let project = parse_layer(Path::new(locations::files::CONFIG), &content)?;
+		let global = match locations::global_config() {
+			Some(path) if path.is_file() => {
+				let table = parse_layer(&path, &read_layer(&path)?)?;
+				Some((path, table))
+			}
 			_ => None,
+		};
+		Ok(Self {
+			content,
+			project,
+			global,
+		})
+	}
+
+	pub fn merged(&self) -> Result<Table> {
+		let mut merged = Table::new();
+		if let Some((path, global)) = &self.global {
+			for (key, value) in global {
+				if !GLOBAL_KEYS.contains(&key.as_str()) {
+					return Err(Error::new(
+						Kind::Config,
+						format!("{} can't be set in {}", key, path.display()),
+					)
+					.hint(format!(
+						"move it to {} or only set {}",
+						locations::files::CONFIG,
+						GLOBAL_KEYS.join(", ")
+					))
+					.into());
+				}
+				merged.insert(key.clone(), value.clone());
+			}
+		}
+		merged.extend(self.project.clone());
+		Ok(merged)
+	}
+
+	pub fn config(&self) -> Result<Config> {
+		let context = || {
+			Error::new(
+				Kind::Config,
+				format!("Failed to parse {}", locations::files::CONFIG),
+			)
+		};
+		match &self.global {
+			// Parsing the text directly keeps line numbers in errors
+			None => toml::from_str(&self.content).with_context(context),
+			Some(_) => Config::deserialize(Value::Table(self.merged()?)).with_context(context),
+		}
+	}
+
+	pub fn source(&self, key: &str) -> Source {
+		if self.project.contains_key(key) {
+			return Source::Project;
+		}
+		match &self.global {
+			Some((path, global)) if global.contains_key(key) => Source::Global(path.clone()),
+			_ => Source::Default,
 		}
 	}
 }
 
-impl Default for Format {
-	fn default() -> Self { Self::LaTeX }
+fn read_layer(path: &Path) -> Result<String> {
+	fs::read_to_string(path).context(Error::new(
+		Kind::Config,
+		format!("Failed to read {}", path.display()),
+	))
+}
+
+fn parse_layer(path: &Path, content: &str) -> Result<Table> {
+	toml::from_str(content).context(Error::new(
+		Kind::Config,
+		format!("Failed to parse {}", path.display()),
+	))
+}
+
+impl fmt::Display for Source {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Source::Project => write!(f, "{}", locations::files::CONFIG),
+			Source::Global(path) => write!(f, "{}", path.display()),
+			Source::Default => write!(f, "default"),
+		}
+	}
+}
+
+impl Term {
+	pub fn contains(&self, date: NaiveDate) -> bool { self.start <= date && date <= self.end }
+
+	// Term names are used as is for archive folders, so anything a folder or tag name can't hold
+	// is turned away instead of being rewritten
+	pub fn name_problem(name: &str) -> Option<&'static str> {
+		if name.trim().is_empty() {
+			Some("is empty")
+		} else if name.contains(['/', '\\']) || name == "." || name == ".." {
+			Some("can't be used as a folder name")
+		} else if !git2::Reference::is_valid_name(&format!("refs/tags/{}", Term::tag(name))) {
+			Some("can't be used in a git tag name")
+		} else {
+			None
+		}
+	}
+
+	pub fn folder(name: &str) -> PathBuf { Path::new(locations::folders::ARCHIVE).join(name) }
+
+	pub fn tag(name: &str) -> String {
+		format!(
+			"archive/{}",
+			name.split_whitespace().collect::<Vec<&str>>().join("-")
+		)
+	}
 }
 
 impl fmt::Display for Class {
@@ -92,10 +598,18 @@ impl fmt::Display for Class {
 
 #[cfg(test)]
 mod test {
+	use std::collections::HashMap;
+	use std::path::PathBuf;
+	use std::str::FromStr;
+
+	use chrono::NaiveDate;
 	use toml::de::Error;
 
-	use super::Format;
-	use crate::conf::{Class, Config};
+	use super::{defaults, Format};
+	use crate::conf::{
+		Bibliography, Class, Config, Engine, Layers, MarkdownConverter, Source, Target, Term,
+	};
+	use crate::layout::Layout;
 
 	#[test]
 	fn read_config() -> Result<(), Error> {
@@ -115,13 +629,33 @@ mod test {
 				delimiter: String::from(">"),
 				open_with: None,
 				view_with: None,
+				latexmk: false,
 				default_format: Format::Markdown,
 				classes: vec![Class {
 					name: String::from("AP Physics 2"),
+					renamed_from: Vec::new(),
 					teacher: String::from("Mr. Feynman"),
-					active: true
+					active: None,
+					terms: Vec::new(),
+					engine: None,
+					bibliography: None,
+					outputs: None,
+					reference_doc: None,
+					format: None,
+					branch_template: None,
+					root_template: None,
+					weights: HashMap::new(),
+					credits: None
 				}],
-				latexmk: false
+				engine: None,
+				max_reruns: 3,
+				bibliography: None,
+				markdown_converter: MarkdownConverter::Pandoc,
+				outputs: vec![Target::Pdf],
+				type_outputs: HashMap::new(),
+				grade_scale: defaults::grade_scale(),
+				layout: Layout::default(),
+				terms: Vec::new()
 			}
 		);
 		// Custom default_format
@@ -142,13 +676,33 @@ mod test {
 				delimiter: String::from(">"),
 				open_with: Some(vec![String::from("code")]),
 				view_with: None,
+				latexmk: false,
 				default_format: Format::LaTeX,
 				classes: vec![Class {
 					name: String::from("AP Physics 2"),
+					renamed_from: Vec::new(),
 					teacher: String::from("Mr. Feynman"),
-					active: true
+					active: None,
+					terms: Vec::new(),
+					engine: None,
+					bibliography: None,
+					outputs: None,
+					reference_doc: None,
+					format: None,
+					branch_template: None,
+					root_template: None,
+					weights: HashMap::new(),
+					credits: None
 				}],
-				latexmk: false
+				engine: None,
+				max_reruns: 3,
+				bibliography: None,
+				markdown_converter: MarkdownConverter::Pandoc,
+				outputs: vec![Target::Pdf],
+				type_outputs: HashMap::new(),
+				grade_scale: defaults::grade_scale(),
+				layout: Layout::default(),
+				terms: Vec::new()
 			}
 		);
 		// Multiple classes
@@ -158,15 +712,39 @@ mod test {
         author = \"Matt Gleich\"
         open_with = [\"code\"]
         default_format = \"LaTeX\"
+        engine = \"tectonic\"
+        markdown_converter = \"native\"
+        outputs = [\"pdf\", \"html\"]
+
+        [bibliography]
+        files = [\"library.bib\"]
+        csl = \"apa.csl\"
+
+        [type_outputs]
+        Note = [\"html\"]
+
+        [[terms]]
+        name = \"Fall 2022\"
+        start = 2022-09-01
+        end = \"2023-01-20\"
 
         [[classes]]
         name = \"AP Physics 2\"
         teacher = \"Mr. Feynman\"
+        credits = 4.0
+        terms = [\"Fall 2022\"]
+        weights = { Assessment = 60, Lab = 40 }
 
         [[classes]]
         name = \"AP Chemistry 2\"
         teacher = \"Mr. White\"
 		active = false
+		engine = \"latexmk:xelatex\"
+		bibliography = { files = [\"chemistry.bib\"] }
+		outputs = [\"pdf\", \"docx\"]
+		reference_doc = \"templates/chemistry.docx\"
+		format = \"Markdown\"
+		branch_template = \"lab\"
     "
 			)?,
 			Config {
@@ -174,22 +752,202 @@ mod test {
 				delimiter: String::from(">"),
 				open_with: Some(vec![String::from("code")]),
 				view_with: None,
+				latexmk: false,
 				default_format: Format::LaTeX,
 				classes: vec![
 					Class {
 						name: String::from("AP Physics 2"),
+						renamed_from: Vec::new(),
 						teacher: String::from("Mr. Feynman"),
-						active: true
+						active: None,
+						terms: vec![String::from("Fall 2022")],
+						engine: None,
+						bibliography: None,
+						outputs: None,
+						reference_doc: None,
+						format: None,
+						branch_template: None,
+						root_template: None,
+						weights: HashMap::from([
+							(String::from("Assessment"), 60.0),
+							(String::from("Lab"), 40.0)
+						]),
+						credits: Some(4.0)
 					},
 					Class {
 						name: String::from("AP Chemistry 2"),
+						renamed_from: Vec::new(),
 						teacher: String::from("Mr. White"),
-						active: false
+						active: Some(false),
+						terms: Vec::new(),
+						engine: Some(Engine::Latexmk(Box::new(Engine::XeLaTeX))),
+						bibliography: Some(Bibliography {
+							files: vec![PathBuf::from("chemistry.bib")],
+							csl: None
+						}),
+						outputs: Some(vec![Target::Pdf, Target::Docx]),
+						reference_doc: Some(PathBuf::from("templates/chemistry.docx")),
+						format: Some(Format::Markdown),
+						branch_template: Some(String::from("lab")),
+						root_template: None,
+						weights: HashMap::new(),
+						credits: None
 					}
 				],
-				latexmk: false
+				engine: Some(Engine::Tectonic),
+				max_reruns: 3,
+				bibliography: Some(Bibliography {
+					files: vec![PathBuf::from("library.bib")],
+					csl: Some(PathBuf::from("apa.csl"))
+				}),
+				markdown_converter: MarkdownConverter::Native,
+				outputs: vec![Target::Pdf, Target::Html],
+				type_outputs: HashMap::from([(String::from("Note"), vec![Target::Html])]),
+				grade_scale: defaults::grade_scale(),
+				layout: Layout::default(),
+				terms: vec![Term {
+					name: String::from("Fall 2022"),
+					start: NaiveDate::from_ymd_opt(2022, 9, 1).unwrap(),
+					end: NaiveDate::from_ymd_opt(2023, 1, 20).unwrap()
+				}]
 			}
 		);
 		Ok(())
 	}
+
+	#[test]
+	fn parse_engine() -> anyhow::Result<()> {
+		assert_eq!(Engine::from_str("xelatex")?, Engine::XeLaTeX);
+		assert_eq!(
+			Engine::from_str("latexmk")?,
+			Engine::Latexmk(Box::new(Engine::PdfLaTeX))
+		);
+		assert_eq!(
+			Engine::from_str("latexmk:lualatex")?,
+			Engine::Latexmk(Box::new(Engine::LuaLaTeX))
+		);
+		assert_eq!(
+			Engine::from_str("make pdf IN={input} OUT={outdir}")?,
+			Engine::Command(
+				["make", "pdf", "IN={input}", "OUT={outdir}"]
+					.map(String::from)
+					.to_vec()
+			)
+		);
+		let quoted = Engine::from_str("\"/opt/My TeX/build\" --name 'Lab Report' {input}")?;
+		assert_eq!(
+			quoted,
+			Engine::Command(
+				["/opt/My TeX/build", "--name", "Lab Report", "{input}"]
+					.map(String::from)
+					.to_vec()
+			)
+		);
+		assert_eq!(Engine::from_str(&quoted.to_string())?, quoted);
+		assert!(Engine::from_str("build 'oops {input}").is_err());
+		assert!(Engine::from_str("latexmk:tectonic").is_err());
+		assert!(Engine::from_str("word").is_err());
+
+		assert_eq!(
+			Engine::from_magic_comment("% !TEX program = xelatex\n\\documentclass{article}")
+				.transpose()?,
+			Some(Engine::XeLaTeX)
+		);
+		assert_eq!(
+			Engine::from_magic_comment("\\documentclass{article}").transpose()?,
+			None
+		);
+
+		let legacy = toml::from_str::<Config>("author = \"Matt\"\nlatexmk = true")?;
+		assert_eq!(legacy.engine(), Engine::Latexmk(Box::new(Engine::PdfLaTeX)));
+		let both =
+			toml::from_str::<Config>("author = \"Matt\"\nlatexmk = true\nengine = \"xelatex\"")?;
+		assert_eq!(both.engine(), Engine::XeLaTeX);
+		Ok(())
+	}
+
+	#[test]
+	fn active() {
+		let terms = vec![
+			Term {
+				name: String::from("Fall 2022"),
+				start: NaiveDate::from_ymd_opt(2022, 9, 1).unwrap(),
+				end: NaiveDate::from_ymd_opt(2023, 1, 20).unwrap(),
+			},
+			Term {
+				name: String::from("Spring 2023"),
+				start: NaiveDate::from_ymd_opt(2023, 1, 23).unwrap(),
+				end: NaiveDate::from_ymd_opt(2023, 6, 9).unwrap(),
+			},
+		];
+		let mut class = Class {
+			name: String::from("AP Physics 2"),
+			renamed_from: Vec::new(),
+			teacher: String::from("Mr. Feynman"),
+			active: None,
+			terms: vec![String::from("Fall 2022")],
+			engine: None,
+			bibliography: None,
+			outputs: None,
+			reference_doc: None,
+			format: None,
+			branch_template: None,
+			root_template: None,
+			weights: HashMap::new(),
+			credits: None,
+		};
+		let december = NaiveDate::from_ymd_opt(2022, 12, 1).unwrap();
+		let march = NaiveDate::from_ymd_opt(2023, 3, 1).unwrap();
+		assert!(class.is_active(&terms, december));
+		assert!(!class.is_active(&terms, march));
+		class.active = Some(true);
+		assert!(class.is_active(&terms, march));
+		class.active = None;
+		class.terms.clear();
+		assert!(class.is_active(&terms, march));
+	}
+
+	#[test]
+	fn term_names() {
+		assert_eq!(Term::name_problem("Fall 2022"), None);
+		assert_eq!(Term::tag("Fall  2022"), "archive/Fall-2022");
+		assert_eq!(Term::name_problem(" "), Some("is empty"));
+		assert_eq!(
+			Term::name_problem("2022/23"),
+			Some("can't be used as a folder name")
+		);
+		assert_eq!(
+			Term::name_problem("Fall: 2022"),
+			Some("can't be used in a git tag name")
+		);
+		assert_eq!(
+			Term::name_problem("Fall..2022"),
+			Some("can't be used in a git tag name")
+		);
+	}
+
+	#[test]
+	fn layers() -> anyhow::Result<()> {
+		let content = "author = \"Matt Gleich\"\nclasses = []";
+		let global_path = PathBuf::from("/home/matt/.config/optic/config.toml");
+		let mut layers = Layers {
+			content: content.to_string(),
+			project: toml::from_str(content)?,
+			global: Some((
+				global_path.clone(),
+				toml::from_str("author = \"Matt\"\nview_with = [\"zathura\"]")?,
+			)),
+		};
+
+		let config = layers.config()?;
+		assert_eq!(config.author, "Matt Gleich");
+		assert_eq!(config.view_with, Some(vec![String::from("zathura")]));
+		assert_eq!(layers.source("author"), Source::Project);
+		assert_eq!(layers.source("view_with"), Source::Global(global_path));
+		assert_eq!(layers.source("delimiter"), Source::Default);
+
+		layers.global.as_mut().unwrap().1 = toml::from_str("classes = []")?;
+		assert!(layers.config().is_err());
+		Ok(())
+	}
 }
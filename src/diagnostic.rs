@@ -0,0 +1,371 @@
+use std::fmt::{self, Display};
+use std::path::PathBuf;
+
+use colorful::Colorful;
+
+use crate::branch::Branch;
+use crate::conf::Format;
+use crate::locations::files;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Severity {
+	Error,
+	Warning,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Kind {
+	UndefinedControlSequence(String),
+	MissingFile(String),
+	OverfullBox(String),
+	Other(String),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Diagnostic {
+	pub severity: Severity,
+	pub kind: Kind,
+	pub line: Option<usize>,
+	pub context: Option<String>,
+	pub source_line: Option<usize>,
+}
+
+#[derive(Debug)]
+pub struct BuildError {
+	pub log: PathBuf,
+	pub diagnostics: Vec<Diagnostic>,
+}
+
+pub fn parse(log: &str) -> Vec<Diagnostic> {
+	let lines: Vec<&str> = log.lines().collect();
+	let mut diagnostics = Vec::new();
+	for (i, line) in lines.iter().enumerate() {
+		if let Some(message) = line.strip_prefix("! ") {
+			if message.starts_with("Emergency stop") && !diagnostics.is_empty() {
+				continue;
+			}
+			let (line_number, context) = error_location(&lines[i + 1..]);
+			let kind = if message.starts_with("Undefined control sequence") {
+				Kind::UndefinedControlSequence(
+					context
+						.and_then(|c| c.split_whitespace().last())
+						.unwrap_or_default()
+						.to_string(),
+				)
+			} else if let Some(file) = missing_file(message) {
+				Kind::MissingFile(file)
+			} else {
+				Kind::Other(message.trim_end_matches('.').to_string())
+			};
+			diagnostics.push(Diagnostic {
+				severity: Severity::Error,
+				kind,
+				line: line_number,
+				context: None,
+				source_line: None,
+			});
+		} else if line.starts_with("Overfull \\") {
+			let line_number = line.split_once(" at line").and_then(|(_, rest)| {
+				rest.trim_start_matches('s')
+					.trim()
+					.split(|c: char| !c.is_ascii_digit())
+					.next()
+					.and_then(|n| n.parse().ok())
+			});
+			diagnostics.push(Diagnostic {
+				severity: Severity::Warning,
+				kind: Kind::OverfullBox(
+					line.split_once(" in ")
+						.or_else(|| line.split_once(" detected "))
+						.map_or(*line, |(summary, _)| summary)
+						.to_string(),
+				),
+				line: line_number,
+				context: None,
+				source_line: None,
+			});
+		} else if let Some(file) = line.strip_prefix("LaTeX Warning: ").and_then(missing_file) {
+			diagnostics.push(Diagnostic {
+				severity: Severity::Warning,
+				kind: Kind::MissingFile(file),
+				line: None,
+				context: None,
+				source_line: None,
+			});
+		}
+	}
+	diagnostics
+}
+
+pub fn parse_typst(log: &str) -> Vec<Diagnostic> {
+	let lines: Vec<&str> = log.lines().collect();
+	let mut diagnostics = Vec::new();
+	for (i, line) in lines.iter().enumerate() {
+		let (severity, message) = if let Some(message) = line.strip_prefix("error: ") {
+			(Severity::Error, message)
+		} else if let Some(message) = line.strip_prefix("warning: ") {
+			(Severity::Warning, message)
+		} else {
+			continue;
+		};
+		let line_number = lines[i + 1..]
+			.iter()
+			.take(3)
+			.find_map(|l| l.trim().strip_prefix("┌─"))
+			.and_then(|location| {
+				let mut parts = location.trim().rsplitn(3, ':');
+				let _column = parts.next();
+				parts.next()?.parse().ok()
+			});
+		let kind = match missing_typst_file(message) {
+			Some(file) => Kind::MissingFile(file),
+			None => Kind::Other(message.to_string()),
+		};
+		diagnostics.push(Diagnostic {
+			severity,
+			kind,
+			line: line_number,
+			context: None,
+			source_line: None,
+		});
+	}
+	diagnostics
+}
+
+fn missing_typst_file(message: &str) -> Option<String> {
+	let (_, rest) = message.split_once("file not found (searched at ")?;
+	Some(rest.strip_suffix(')').unwrap_or(rest).to_string())
+}
+
+fn error_location<'a>(lines: &[&'a str]) -> (Option<usize>, Option<&'a str>) {
+	for line in lines.iter().take(20) {
+		if line.starts_with("! ") && !line.starts_with("! Emergency stop") {
+			break;
+		}
+		if let Some(rest) = line.strip_prefix("l.") {
+			let (number, context) = rest.split_once(' ').unwrap_or((rest, ""));
+			if let Ok(number) = number.parse() {
+				return (Some(number), Some(context));
+			}
+		}
+	}
+	(None, None)
+}
+
+fn missing_file(message: &str) -> Option<String> {
+	let (_, rest) = message.split_once("File `")?;
+	let (file, rest) = rest.split_once('\'')?;
+	if rest.trim_start().starts_with("not found") {
+		Some(file.to_string())
+	} else {
+		None
+	}
+}
+
+impl Diagnostic {
+	pub fn resolve(&mut self, latex: &str, source: &str, content_offset: Option<usize>) {
+		let line = match self.line {
+			Some(line) => line,
+			None => return,
+		};
+		self.context = line
+			.checked_sub(1)
+			.and_then(|i| latex.lines().nth(i))
+			.map(|l| l.trim_end().to_string());
+
+		let source_lines = source.lines().count();
+		self.source_line = match content_offset {
+			Some(offset) if line > offset && line - offset <= source_lines => Some(line - offset),
+			_ => {
+				let context = self.context.as_deref().unwrap_or_default().trim();
+				let mut matches = source
+					.lines()
+					.enumerate()
+					.filter(|(_, l)| !context.is_empty() && l.trim() == context);
+				match (matches.next(), matches.next()) {
+					(Some((i, _)), None) => Some(i + 1),
+					_ => None,
+				}
+			}
+		};
+	}
+}
+
+impl Display for Kind {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Kind::UndefinedControlSequence(name) if name.is_empty() => {
+				write!(f, "Undefined control sequence")
+			}
+			Kind::UndefinedControlSequence(name) => {
+				write!(f, "Undefined control sequence {}", name)
+			}
+			Kind::MissingFile(file) => write!(f, "File {} not found", file),
+			Kind::OverfullBox(summary) => write!(f, "{}", summary),
+			Kind::Other(message) => write!(f, "{}", message),
+		}
+	}
+}
+
+impl Display for BuildError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"Failed to generate PDF. Please check {}",
+			self.log.display()
+		)
+	}
+}
+
+impl std::error::Error for BuildError {}
+
+pub fn print(branch: &Branch, diagnostics: &[Diagnostic], indent: &str) {
+	let build_file = match branch.format {
+		Format::Typst => files::TYPST_BUILD,
+		_ => files::LATEX_BUILD,
+	};
+	for diagnostic in diagnostics {
+		let label = match diagnostic.severity {
+			Severity::Error => "error".red().bold().to_string(),
+			Severity::Warning => "warning".yellow().bold().to_string(),
+		};
+		println!("{}{}: {}", indent, label, diagnostic.kind);
+
+		match (diagnostic.source_line, diagnostic.line) {
+			(Some(source_line), Some(line)) => {
+				println!(
+					"{}  --> {}:{} ({}:{})",
+					indent,
+					branch.path.display(),
+					source_line,
+					build_file,
+					line
+				)
+			}
+			(None, Some(line)) => println!("{}  --> {}:{}", indent, build_file, line),
+			_ => (),
+		}
+		if let Some(context) = &diagnostic.context {
+			println!("{}   | {}", indent, context.trim().dim());
+		}
+	}
+}
+
+pub fn report(branch: &Branch, err: &anyhow::Error, indent: &str) {
+	if let Some(build_error) = err.downcast_ref::<BuildError>() {
+		print(branch, &build_error.diagnostics, indent);
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use crate::diagnostic::{parse, parse_typst, Diagnostic, Kind, Severity};
+
+	#[test]
+	fn parse_log() {
+		let log = "\
+This is pdfTeX, Version 3.141592653-2.6-1.40.24
+(./main.tex
+Overfull \\hbox (15.0pt too wide) in paragraph at lines 10--11
+[]\\OT1/cmr/m/n/10 Hello
+
+! Undefined control sequence.
+l.12 \\FAIL
+
+! LaTeX Error: File `missing.sty' not found.
+
+Type X to quit or <RETURN> to proceed,
+or enter new name. (Default extension: sty)
+
+Enter file name:
+! Emergency stop.
+<read *>
+
+l.3 \\usepackage
+";
+		assert_eq!(
+			parse(log),
+			vec![
+				Diagnostic {
+					severity: Severity::Warning,
+					kind: Kind::OverfullBox(String::from("Overfull \\hbox (15.0pt too wide)")),
+					line: Some(10),
+					context: None,
+					source_line: None,
+				},
+				Diagnostic {
+					severity: Severity::Error,
+					kind: Kind::UndefinedControlSequence(String::from("\\FAIL")),
+					line: Some(12),
+					context: None,
+					source_line: None,
+				},
+				Diagnostic {
+					severity: Severity::Error,
+					kind: Kind::MissingFile(String::from("missing.sty")),
+					line: Some(3),
+					context: None,
+					source_line: None,
+				},
+			]
+		);
+	}
+
+	#[test]
+	fn parse_typst_log() {
+		let log = "\
+error: unknown variable: foo
+  ┌─ main.typ:5:2
+  │
+5 │ #foo
+  │  ^^^
+
+error: file not found (searched at imgs/graph.png)
+";
+		assert_eq!(
+			parse_typst(log),
+			vec![
+				Diagnostic {
+					severity: Severity::Error,
+					kind: Kind::Other(String::from("unknown variable: foo")),
+					line: Some(5),
+					context: None,
+					source_line: None,
+				},
+				Diagnostic {
+					severity: Severity::Error,
+					kind: Kind::MissingFile(String::from("imgs/graph.png")),
+					line: None,
+					context: None,
+					source_line: None,
+				},
+			]
+		);
+	}
+
+	#[test]
+	fn resolve() {
+		let latex = "\\documentclass{article}\n\\begin{document}\nHello\n\\FAIL\n\\end{document}\n";
+		let source = "Hello\n\\FAIL\n";
+
+		let mut diagnostic = Diagnostic {
+			severity: Severity::Error,
+			kind: Kind::UndefinedControlSequence(String::from("\\FAIL")),
+			line: Some(4),
+			context: None,
+			source_line: None,
+		};
+		diagnostic.resolve(latex, source, Some(2));
+		assert_eq!(diagnostic.context, Some(String::from("\\FAIL")));
+		assert_eq!(diagnostic.source_line, Some(2));
+
+		diagnostic.source_line = None;
+		diagnostic.resolve(latex, source, None);
+		assert_eq!(diagnostic.source_line, Some(2));
+
+		diagnostic.line = Some(0);
+		diagnostic.resolve(latex, source, Some(2));
+		assert_eq!(diagnostic.context, None);
+		assert_eq!(diagnostic.source_line, None);
+	}
+}
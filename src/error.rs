@@ -0,0 +1,119 @@
+use std::{fmt, io};
+
+use colorful::Colorful;
+
+use crate::diagnostic::BuildError;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Kind {
+	Config,
+	Build,
+	Cancelled,
+}
+
+#[derive(Debug)]
+pub struct Error {
+	pub kind: Kind,
+	message: String,
+	hint: Option<String>,
+}
+
+impl Error {
+	pub fn new<M: Into<String>>(kind: Kind, message: M) -> Self {
+		Self {
+			kind,
+			message: message.into(),
+			hint: None,
+		}
+	}
+
+	pub fn hint<H: Into<String>>(mut self, hint: H) -> Self {
+		self.hint = Some(hint.into());
+		self
+	}
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { write!(f, "{}", self.message) }
+}
+
+impl std::error::Error for Error {}
+
+impl Kind {
+	pub fn code(&self) -> i32 {
+		match self {
+			Kind::Config => 2,
+			Kind::Build => 3,
+			Kind::Cancelled => 130,
+		}
+	}
+}
+
+// Contexts aren't visible through chain(), so downcast the whole error instead
+pub fn kind(err: &anyhow::Error) -> Option<Kind> {
+	if let Some(error) = err.downcast_ref::<Error>() {
+		return Some(error.kind);
+	}
+	if err.is::<BuildError>() {
+		return Some(Kind::Build);
+	}
+	match err.downcast_ref::<io::Error>() {
+		Some(io_err) if io_err.kind() == io::ErrorKind::Interrupted => Some(Kind::Cancelled),
+		_ => None,
+	}
+}
+
+pub fn code(err: &anyhow::Error) -> i32 { kind(err).map_or(1, |k| k.code()) }
+
+pub fn hint(err: &anyhow::Error) -> Option<&str> {
+	err.downcast_ref::<Error>().and_then(|e| e.hint.as_deref())
+}
+
+pub fn report(err: &anyhow::Error) {
+	if kind(err) == Some(Kind::Cancelled) {
+		eprintln!("{}", "Cancelled".yellow());
+		return;
+	}
+
+	let mut chain = err.chain();
+	if let Some(first) = chain.next() {
+		eprintln!("{} {}", "error:".red().bold(), first);
+	}
+	for cause in chain {
+		eprintln!("  {} {}", "caused by:".red(), cause);
+	}
+	if let Some(hint) = hint(err) {
+		eprintln!("  {} {}", "hint:".cyan().bold(), hint);
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use std::io;
+
+	use anyhow::{anyhow, Context};
+
+	use super::{code, Error, Kind};
+
+	#[test]
+	fn codes() {
+		assert_eq!(code(&anyhow!("Failed to open repo")), 1);
+		assert_eq!(
+			code(
+				&anyhow!("missing field `author`")
+					.context(Error::new(Kind::Config, "Failed to parse optic.toml"))
+					.context("Failed to read configuration")
+			),
+			2
+		);
+		assert_eq!(
+			code(
+				&anyhow::Error::from(io::Error::from(io::ErrorKind::Interrupted))
+					.context("Failed to ask")
+			),
+			130
+		);
+		let result: Result<(), io::Error> = Err(io::Error::from(io::ErrorKind::NotFound));
+		assert_eq!(code(&result.context("Failed to read").unwrap_err()), 1);
+	}
+}
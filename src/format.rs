@@ -0,0 +1,283 @@
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use anyhow::{bail, Context, Result};
+use chrono::{Datelike, NaiveDate};
+use ordinal::Ordinal;
+
+use crate::branch::Branch;
+use crate::conf::{Config, Format, MarkdownConverter};
+use crate::header::Style;
+use crate::markdown;
+
+pub trait Plugin: Sync {
+	fn format(&self) -> Format;
+
+	fn extension(&self) -> &'static str;
+
+	fn template_suffix(&self) -> String { format!("{}.hbs", self.extension()) }
+
+	fn comment(&self) -> (&'static str, &'static str);
+
+	fn front_matter(&self) -> Style;
+
+	fn escape(&self, s: &str) -> String;
+
+	fn superscript(&self, text: &str) -> String;
+
+	fn date(&self, date: NaiveDate) -> String {
+		date.format(&format!(
+			"%A, %B %e{}, %Y",
+			self.superscript(Ordinal(date.day()).suffix())
+		))
+		.to_string()
+	}
+
+	fn root_format(&self) -> Format { Format::LaTeX }
+
+	fn imgs_dir(&self, branch: &Branch) -> String {
+		format!(
+			"{{{}/}}",
+			self.escape(branch.relative_imgs_dir().to_str().unwrap())
+		)
+	}
+
+	fn pandoc_reader(&self) -> &'static str;
+
+	fn source(&self, path: &Path) -> Result<String> {
+		fs::read_to_string(path).context(format!("Failed to read {}", path.display()))
+	}
+
+	fn to_latex(&self, branch: &Branch, config: &Config) -> Result<String> {
+		branch.pandoc(&branch.bibliography(config), "latex", &[])
+	}
+
+	fn to_html(&self, branch: &Branch, config: &Config) -> Result<String> {
+		branch.pandoc(&branch.bibliography(config), "html", &["--mathjax"])
+	}
+}
+
+pub struct LaTeX;
+pub struct Markdown;
+pub struct Typst;
+pub struct AsciiDoc;
+pub struct ReStructuredText;
+
+// Every format optic knows about. Format::plugin and Format::from_path look formats up here
+pub static PLUGINS: [&dyn Plugin; 5] = [&LaTeX, &Markdown, &Typst, &AsciiDoc, &ReStructuredText];
+
+impl Plugin for LaTeX {
+	fn format(&self) -> Format { Format::LaTeX }
+
+	fn extension(&self) -> &'static str { ".tex" }
+
+	fn comment(&self) -> (&'static str, &'static str) { ("\\iffalse", "\\fi") }
+
+	fn front_matter(&self) -> Style { Style::Toml("%") }
+
+	fn escape(&self, s: &str) -> String {
+		let mut output = String::new();
+		let mut previous = None;
+		for c in s.chars() {
+			if previous.replace(c) == Some('\\') {
+				output.push(c);
+				continue;
+			}
+			match c {
+				'&' => output.push_str("\\&"),
+				'$' => output.push_str("\\$"),
+				'#' => output.push_str("\\#"),
+				'%' => output.push_str("\\%"),
+				_ => output.push(c),
+			}
+		}
+		output
+	}
+
+	fn superscript(&self, text: &str) -> String { format!("\\textsuperscript{{{}}}", text) }
+
+	fn pandoc_reader(&self) -> &'static str { "latex" }
+
+	fn to_latex(&self, branch: &Branch, _: &Config) -> Result<String> {
+		Ok(fs::read_to_string(&branch.path)?)
+	}
+}
+
+impl Plugin for Markdown {
+	fn format(&self) -> Format { Format::Markdown }
+
+	fn extension(&self) -> &'static str { ".md" }
+
+	fn comment(&self) -> (&'static str, &'static str) { ("<!--", "-->") }
+
+	fn front_matter(&self) -> Style { Style::Yaml }
+
+	fn escape(&self, s: &str) -> String { s.to_string() }
+
+	fn superscript(&self, text: &str) -> String { format!("^{}^", text) }
+
+	fn pandoc_reader(&self) -> &'static str { "markdown-auto_identifiers" }
+
+	fn to_latex(&self, branch: &Branch, config: &Config) -> Result<String> {
+		match config.markdown_converter {
+			MarkdownConverter::Native => {
+				Ok(markdown::to_latex(
+					&fs::read_to_string(&branch.path)?,
+					&branch.relative_imgs_dir(),
+				))
+			}
+			MarkdownConverter::Pandoc => branch.pandoc(&branch.bibliography(config), "latex", &[]),
+		}
+	}
+
+	fn to_html(&self, branch: &Branch, config: &Config) -> Result<String> {
+		match config.markdown_converter {
+			MarkdownConverter::Native => Ok(markdown::to_html(&fs::read_to_string(&branch.path)?)),
+			MarkdownConverter::Pandoc => {
+				branch.pandoc(&branch.bibliography(config), "html", &["--mathjax"])
+			}
+		}
+	}
+}
+
+impl Plugin for Typst {
+	fn format(&self) -> Format { Format::Typst }
+
+	fn extension(&self) -> &'static str { ".typ" }
+
+	fn comment(&self) -> (&'static str, &'static str) { ("/*", "*/") }
+
+	fn front_matter(&self) -> Style { Style::Toml("//") }
+
+	fn escape(&self, s: &str) -> String {
+		backslash_escape(s, &['\\', '#', '$', '*', '_', '`', '@', '<', '[', ']', '~'])
+	}
+
+	fn superscript(&self, text: &str) -> String { format!("#super[{}]", text) }
+
+	fn root_format(&self) -> Format { Format::Typst }
+
+	fn imgs_dir(&self, branch: &Branch) -> String {
+		format!("/{}/", branch.imgs_dir.to_str().unwrap())
+	}
+
+	fn pandoc_reader(&self) -> &'static str { "typst" }
+}
+
+impl Plugin for AsciiDoc {
+	fn format(&self) -> Format { Format::AsciiDoc }
+
+	fn extension(&self) -> &'static str { ".adoc" }
+
+	fn comment(&self) -> (&'static str, &'static str) { ("////", "////") }
+
+	fn front_matter(&self) -> Style { Style::Toml("//") }
+
+	fn escape(&self, s: &str) -> String {
+		backslash_escape(s, &['\\', '*', '_', '`', '#', '^', '~', '{', '+'])
+	}
+
+	fn superscript(&self, text: &str) -> String { format!("^{}^", text) }
+
+	fn pandoc_reader(&self) -> &'static str { "docbook" }
+
+	fn source(&self, path: &Path) -> Result<String> {
+		let output = Command::new("asciidoctor")
+			.args(["-b", "docbook5", "-o", "-"])
+			.arg(path)
+			.stdout(Stdio::piped())
+			.stderr(Stdio::piped())
+			.output()
+			.context("Failed to run asciidoctor. Install it to build AsciiDoc branches")?;
+		if !output.status.success() {
+			bail!(
+				"asciidoctor exited with {}: {}",
+				output.status,
+				String::from_utf8_lossy(&output.stderr).trim()
+			);
+		}
+		String::from_utf8(output.stdout).context("asciidoctor produced invalid UTF-8")
+	}
+}
+
+impl Plugin for ReStructuredText {
+	fn format(&self) -> Format { Format::ReStructuredText }
+
+	fn extension(&self) -> &'static str { ".rst" }
+
+	// A blank line doesn't end an indented comment, so the header is closed by a second empty one
+	fn comment(&self) -> (&'static str, &'static str) { ("..", "..") }
+
+	fn front_matter(&self) -> Style { Style::Toml("..") }
+
+	fn escape(&self, s: &str) -> String { backslash_escape(s, &['\\', '*', '_', '`', '|']) }
+
+	fn superscript(&self, text: &str) -> String { format!("\\ :sup:`{}`", text) }
+
+	fn pandoc_reader(&self) -> &'static str { "rst" }
+}
+
+fn backslash_escape(s: &str, special: &[char]) -> String {
+	let mut output = String::new();
+	for c in s.chars() {
+		if special.contains(&c) {
+			output.push('\\');
+		}
+		output.push(c);
+	}
+	output
+}
+
+#[cfg(test)]
+mod test {
+	use chrono::NaiveDate;
+	use strum::IntoEnumIterator;
+
+	use crate::conf::Format;
+
+	#[test]
+	fn registry() {
+		for format in Format::iter() {
+			assert_eq!(format.plugin().format(), format);
+			assert_eq!(
+				Format::from_path(format!("lab{}", format.extension()).as_ref()),
+				Some(format)
+			);
+		}
+	}
+
+	#[test]
+	fn escape() {
+		assert_eq!(
+			Format::LaTeX.plugin().escape("Lab #2: 50% & \\$5"),
+			"Lab \\#2: 50\\% \\& \\$5"
+		);
+		assert_eq!(
+			Format::Typst.plugin().escape("Lab #2: $5 *off* [draft]"),
+			"Lab \\#2: \\$5 \\*off\\* \\[draft\\]"
+		);
+		assert_eq!(
+			Format::ReStructuredText.plugin().escape("*Lab* `one`"),
+			"\\*Lab\\* \\`one\\`"
+		);
+		assert_eq!(Format::Markdown.plugin().escape("*Lab*"), "*Lab*");
+	}
+
+	#[test]
+	fn date() {
+		let date = NaiveDate::from_ymd_opt(2022, 10, 1).unwrap();
+		assert_eq!(
+			Format::LaTeX.plugin().date(date),
+			"Saturday, October  1\\textsuperscript{st}, 2022"
+		);
+		assert_eq!(
+			Format::AsciiDoc.plugin().date(date),
+			"Saturday, October  1^st^, 2022"
+		);
+		assert_eq!(
+			Format::ReStructuredText.plugin().date(date),
+			"Saturday, October  1\\ :sup:`st`, 2022"
+		);
+	}
+}
@@ -0,0 +1,272 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use crate::branch::Branch;
+use crate::conf::{Class, DocumentType, Grade};
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Report {
+	pub classes: Vec<ClassReport>,
+	pub gpa: Option<f64>,
+	// Printed next to the report rather than being part of it
+	#[serde(skip)]
+	pub warnings: Vec<String>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct ClassReport {
+	pub class: String,
+	pub credits: f64,
+	pub average: f64,
+	pub letter: String,
+	pub points: f64,
+	pub categories: Vec<Category>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Category {
+	#[serde(rename = "type")]
+	pub doc_type: String,
+	pub graded: usize,
+	pub weight: Option<f64>,
+	pub average: f64,
+}
+
+#[derive(Default)]
+struct Tally {
+	earned: f64,
+	possible: f64,
+	graded: usize,
+}
+
+pub fn report(classes: &[Class], branches: &[Branch], scale: &[Grade]) -> Report {
+	let mut reports = Vec::new();
+	let mut warnings = Vec::new();
+	for class in classes {
+		let mut keys: Vec<&String> = class.weights.keys().collect();
+		keys.sort();
+		for key in keys {
+			if DocumentType::from_str(key).is_err() {
+				warnings.push(format!(
+					"{} in the weights of {} is not a document type",
+					key, class.name
+				));
+			}
+		}
+
+		let mut tallies: BTreeMap<String, Tally> = BTreeMap::new();
+		for branch in branches.iter().filter(|b| b.class.name == class.name) {
+			let score = match branch.metadata.score {
+				Some(score) => score,
+				None => continue,
+			};
+			let weight = branch.metadata.weight.unwrap_or(1.0);
+			let tally = tallies.entry(branch.doc_type.to_string()).or_default();
+			tally.earned += score * weight;
+			tally.possible += branch.metadata.out_of.unwrap_or(100.0) * weight;
+			tally.graded += 1;
+		}
+		tallies.retain(|_, t| t.possible > 0.0);
+		if tallies.is_empty() {
+			continue;
+		}
+
+		let categories: Vec<Category> = tallies
+			.iter()
+			.map(|(doc_type, tally)| {
+				Category {
+					doc_type: doc_type.clone(),
+					graded: tally.graded,
+					weight: class.weights.get(doc_type).copied(),
+					average: tally.earned / tally.possible * 100.0,
+				}
+			})
+			.collect();
+		let weighted: Vec<(f64, f64)> = categories
+			.iter()
+			.filter_map(|c| c.weight.map(|w| (c.average, w)))
+			.filter(|(_, w)| *w > 0.0)
+			.collect();
+		// Once a class has weights, only weighted categories count toward its average
+		if !weighted.is_empty() {
+			for category in categories.iter().filter(|c| c.weight.is_none()) {
+				warnings.push(format!(
+					"{} in {} has graded branches but no weight, so it doesn't count toward the \
+					 average",
+					category.doc_type, class.name
+				));
+			}
+		}
+		let average = if weighted.is_empty() {
+			let earned: f64 = tallies.values().map(|t| t.earned).sum();
+			let possible: f64 = tallies.values().map(|t| t.possible).sum();
+			earned / possible * 100.0
+		} else {
+			weighted.iter().map(|(a, w)| a * w).sum::<f64>()
+				/ weighted.iter().map(|(_, w)| w).sum::<f64>()
+		};
+		let grade = letter(scale, average);
+
+		reports.push(ClassReport {
+			class: class.name.clone(),
+			credits: class.credits.unwrap_or(1.0),
+			average,
+			letter: grade.map(|g| g.letter.clone()).unwrap_or_default(),
+			points: grade.map_or(0.0, |g| g.points),
+			categories,
+		});
+	}
+
+	let credits: f64 = reports.iter().map(|r| r.credits).sum();
+	Report {
+		gpa: if credits > 0.0 {
+			Some(reports.iter().map(|r| r.points * r.credits).sum::<f64>() / credits)
+		} else {
+			None
+		},
+		classes: reports,
+		warnings,
+	}
+}
+
+fn letter(scale: &[Grade], average: f64) -> Option<&Grade> {
+	let mut scale: Vec<&Grade> = scale.iter().collect();
+	scale.sort_by(|a, b| b.min.total_cmp(&a.min));
+	scale
+		.iter()
+		.find(|g| average >= g.min)
+		.or_else(|| scale.last())
+		.copied()
+}
+
+#[cfg(test)]
+mod test {
+	use std::collections::HashMap;
+	use std::time::SystemTime;
+
+	use anyhow::Result;
+	use chrono::NaiveDate;
+
+	use super::report;
+	use crate::branch::Branch;
+	use crate::conf::{Class, DocumentType, Format, Grade};
+	use crate::layout::Layout;
+	use crate::template::RootTemplate;
+
+	fn physics() -> Class {
+		Class {
+			name: String::from("AP Physics 2"),
+			renamed_from: Vec::new(),
+			teacher: String::from("Mr. Feynman"),
+			active: None,
+			terms: Vec::new(),
+			engine: None,
+			bibliography: None,
+			outputs: None,
+			reference_doc: None,
+			format: None,
+			branch_template: None,
+			root_template: None,
+			weights: HashMap::from([
+				(String::from("Assessment"), 60.0),
+				(String::from("Lab"), 40.0),
+			]),
+			credits: Some(2.0),
+		}
+	}
+
+	fn graded(
+		class: &Class,
+		doc_type: DocumentType,
+		score: f64,
+		out_of: Option<f64>,
+		weight: Option<f64>,
+	) -> Result<Branch> {
+		let mut branch = Branch::new(
+			String::from("Graded"),
+			Format::Markdown,
+			doc_type,
+			class.clone(),
+			None,
+			RootTemplate::from_filename("base.tex.hbs"),
+			NaiveDate::from_ymd_opt(2022, 10, 1).unwrap(),
+			SystemTime::now(),
+			&Layout::default(),
+		)?;
+		branch.metadata.score = Some(score);
+		branch.metadata.out_of = out_of;
+		branch.metadata.weight = weight;
+		Ok(branch)
+	}
+
+	#[test]
+	fn weighted() -> Result<()> {
+		let physics = physics();
+		let mut chemistry = physics.clone();
+		chemistry.name = String::from("AP Chemistry 2");
+		chemistry.weights = HashMap::new();
+		chemistry.credits = None;
+
+		let branches = vec![
+			graded(&physics, DocumentType::Assessment, 45.0, Some(50.0), None)?,
+			graded(&physics, DocumentType::Assessment, 70.0, None, Some(2.0))?,
+			graded(&physics, DocumentType::Lab, 19.0, Some(20.0), None)?,
+			graded(&chemistry, DocumentType::Worksheet, 8.0, Some(10.0), None)?,
+			graded(&chemistry, DocumentType::Lab, 9.0, Some(10.0), None)?,
+		];
+		let scale = vec![
+			Grade {
+				letter: String::from("A"),
+				min: 90.0,
+				points: 4.0,
+			},
+			Grade {
+				letter: String::from("B"),
+				min: 80.0,
+				points: 3.0,
+			},
+			Grade {
+				letter: String::from("F"),
+				min: 0.0,
+				points: 0.0,
+			},
+		];
+
+		let report = report(&[physics, chemistry], &branches, &scale);
+		// Assessments: (45 + 70 * 2) / (50 + 100 * 2) = 74%, Labs: 95%
+		assert!((report.classes[0].average - (74.0 * 0.6 + 95.0 * 0.4)).abs() < 1e-9);
+		assert_eq!(report.classes[0].letter, "B");
+		assert!((report.classes[1].average - 85.0).abs() < 1e-9);
+		assert_eq!(report.classes[1].categories.len(), 2);
+		assert_eq!(report.gpa, Some(3.0));
+		assert!(report.warnings.is_empty());
+		Ok(())
+	}
+
+	#[test]
+	fn unweighted() -> Result<()> {
+		let mut class = physics();
+		class.weights = HashMap::from([
+			(String::from("Assessment"), 100.0),
+			(String::from("Assessments"), 50.0),
+		]);
+		let branches = vec![
+			graded(&class, DocumentType::Assessment, 45.0, Some(50.0), None)?,
+			graded(&class, DocumentType::Lab, 10.0, Some(20.0), None)?,
+		];
+
+		let report = report(&[class], &branches, &[]);
+		assert!((report.classes[0].average - 90.0).abs() < 1e-9);
+		assert_eq!(
+			report.warnings,
+			vec![
+				"Assessments in the weights of AP Physics 2 is not a document type",
+				"Lab in AP Physics 2 has graded branches but no weight, so it doesn't count \
+				 toward the average",
+			]
+		);
+		Ok(())
+	}
+}
@@ -0,0 +1,549 @@
+use std::collections::{BTreeMap, HashMap};
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use strum::VariantNames;
+use strum_macros::{Display, EnumString, EnumVariantNames};
+
+use crate::conf::{DocumentType, Format};
+
+pub enum Style {
+	Yaml,
+	Toml(&'static str),
+}
+
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+pub struct Header {
+	#[serde(deserialize_with = "date::required")]
+	pub created: NaiveDate,
+	pub root: String,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub class: Option<String>,
+	#[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+	pub doc_type: Option<DocumentType>,
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub bibliography: Vec<PathBuf>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub csl: Option<PathBuf>,
+	#[serde(flatten)]
+	pub metadata: Metadata,
+}
+
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone, Default)]
+pub struct Metadata {
+	#[serde(
+		default,
+		deserialize_with = "date::optional",
+		skip_serializing_if = "Option::is_none"
+	)]
+	pub due: Option<NaiveDate>,
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub tags: Vec<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub status: Option<Status>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub score: Option<f64>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub out_of: Option<f64>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub weight: Option<f64>,
+	#[serde(flatten)]
+	pub custom: BTreeMap<String, Value>,
+}
+
+#[derive(
+	Deserialize, Serialize, Debug, PartialEq, Clone, Copy, Display, EnumString, EnumVariantNames,
+)]
+#[serde(rename_all = "kebab-case")]
+#[strum(serialize_all = "kebab-case")]
+pub enum Status {
+	Todo,
+	InProgress,
+	Submitted,
+	Graded,
+}
+
+struct Block {
+	open: usize,
+	close: Option<usize>,
+	keys: Vec<usize>,
+}
+
+impl Header {
+	pub fn parse(content: &str, format: &Format, delimiter: &str) -> Result<Self> {
+		match front_matter(content, format) {
+			Some((raw, _)) => {
+				match format.plugin().front_matter() {
+					Style::Yaml => {
+						serde_yaml::from_str(&raw).context("Failed to parse YAML front matter")
+					}
+					Style::Toml(_) => {
+						toml::from_str(&raw).context("Failed to parse TOML front matter")
+					}
+				}
+			}
+			None => Ok(legacy(content, format, delimiter)?.0),
+		}
+	}
+
+	pub fn render(&self, format: &Format) -> Result<String> {
+		Ok(match format.plugin().front_matter() {
+			Style::Yaml => format!("---\n{}---\n", serde_yaml::to_string(self)?),
+			Style::Toml(prefix) => {
+				let marker = format!("{} +++\n", prefix);
+				let mut output = marker.clone();
+				for line in toml::to_string(self)?.lines() {
+					if line.is_empty() {
+						output.push_str(prefix);
+					} else {
+						output.push_str(&format!("{} {}", prefix, line));
+					}
+					output.push('\n');
+				}
+				output.push_str(&marker);
+				output
+			}
+		})
+	}
+
+	fn render_legacy(&self, delimiter: &str) -> Result<BTreeMap<String, String>> {
+		let value = serde_json::to_value(self)?;
+		Ok(value
+			.as_object()
+			.unwrap()
+			.iter()
+			.map(|(key, value)| {
+				let value = match value {
+					Value::String(s) => s.clone(),
+					Value::Number(n) if n.as_f64().is_some_and(|n| n.fract() == 0.0) => {
+						(n.as_f64().unwrap() as i64).to_string()
+					}
+					Value::Array(items) => {
+						items
+							.iter()
+							.map(|i| i.as_str().map_or_else(|| i.to_string(), String::from))
+							.collect::<Vec<String>>()
+							.join(", ")
+					}
+					other => other.to_string(),
+				};
+				(key.clone(), format!("{} {} {}", key, delimiter, value))
+			})
+			.collect())
+	}
+}
+
+impl Status {
+	pub fn is_open(&self) -> bool { matches!(self, Status::Todo | Status::InProgress) }
+}
+
+pub fn update(
+	content: &str,
+	format: &Format,
+	delimiter: &str,
+	change: impl FnOnce(&mut Header),
+) -> Result<String> {
+	let lines: Vec<&str> = content.lines().collect();
+	let mut output: Vec<String> = Vec::new();
+	match front_matter(content, format) {
+		Some((_, range)) => {
+			let mut header = Header::parse(content, format, delimiter)?;
+			change(&mut header);
+			output.extend(lines[..*range.start()].iter().map(|l| l.to_string()));
+			output.push(header.render(format)?.trim_end().to_string());
+			output.extend(lines[range.end() + 1..].iter().map(|l| l.to_string()));
+		}
+		None => {
+			let (mut header, block) = legacy(content, format, delimiter)?;
+			change(&mut header);
+			let mut rendered = header.render_legacy(delimiter)?;
+			let last_key = block.keys.last().copied();
+			for (i, line) in lines.iter().enumerate() {
+				if !block.keys.contains(&i) {
+					output.push(line.to_string());
+					continue;
+				}
+				let key = line.split_once(delimiter).unwrap().0.trim();
+				if let Some(line) = rendered.remove(key) {
+					output.push(line);
+				}
+				if Some(i) == last_key {
+					output.extend(std::mem::take(&mut rendered).into_values());
+				}
+			}
+		}
+	}
+	Ok(output.join("\n") + "\n")
+}
+
+pub fn parse_due(due: &str, today: NaiveDate) -> Result<NaiveDate> {
+	let due = due.trim().to_lowercase();
+	if let Ok(date) = NaiveDate::parse_from_str(&due, "%F") {
+		return Ok(date);
+	}
+	match due.as_str() {
+		"today" => return Ok(today),
+		"tomorrow" => return Ok(today + Duration::days(1)),
+		_ => (),
+	}
+	if let Some(offset) = due.strip_prefix('+') {
+		let (amount, unit) = offset.split_at(offset.len().saturating_sub(1));
+		if let Ok(amount) = amount.parse::<i64>() {
+			match unit {
+				"d" => return Ok(today + Duration::days(amount)),
+				"w" => return Ok(today + Duration::weeks(amount)),
+				_ => (),
+			}
+		}
+	}
+	if let Ok(weekday) = Weekday::from_str(&due) {
+		let days =
+			(weekday.num_days_from_monday() + 7 - today.weekday().num_days_from_monday()) % 7;
+		return Ok(today + Duration::days(if days == 0 { 7 } else { days.into() }));
+	}
+	bail!(
+		"{} is not a valid due date. Use a weekday (e.g. friday), an offset (e.g. +3d or +2w), or \
+		 the YYYY-MM-DD format",
+		due
+	)
+}
+
+pub fn migrate(content: &str, format: &Format, delimiter: &str) -> Result<Option<String>> {
+	if front_matter(content, format).is_some() {
+		return Ok(None);
+	}
+	let (header, block) = legacy(content, format, delimiter)?;
+
+	let lines: Vec<&str> = content.lines().collect();
+	let close = block.close.unwrap_or(lines.len() - 1);
+	let block_is_empty = (block.open + 1..close)
+		.filter(|i| !block.keys.contains(i))
+		.all(|i| lines[i].trim().is_empty());
+	let rest: Vec<&str> = lines
+		.iter()
+		.enumerate()
+		.filter(|(i, _)| {
+			let in_block = (block.open..=close).contains(i);
+			!(block.keys.contains(i) || block_is_empty && in_block)
+		})
+		.map(|(_, line)| *line)
+		.collect();
+
+	Ok(Some(format!(
+		"{}\n{}\n",
+		header.render(format)?,
+		rest.join("\n").trim_start_matches('\n')
+	)))
+}
+
+fn front_matter(content: &str, format: &Format) -> Option<(String, RangeInclusive<usize>)> {
+	let style = format.plugin().front_matter();
+	let marker = match style {
+		Style::Yaml => String::from("---"),
+		Style::Toml(prefix) => format!("{} +++", prefix),
+	};
+	let mut lines = content
+		.lines()
+		.enumerate()
+		.skip_while(|(_, l)| l.trim().is_empty());
+	let (start, first) = lines.next()?;
+	if first.trim_end() != marker {
+		return None;
+	}
+
+	let mut raw = String::new();
+	for (i, line) in lines {
+		if line.trim_end() == marker {
+			return Some((raw, start..=i));
+		}
+		match style {
+			Style::Yaml => raw.push_str(line),
+			Style::Toml(prefix) => {
+				let line = line.strip_prefix(prefix)?;
+				raw.push_str(line.strip_prefix(' ').unwrap_or(line));
+			}
+		}
+		raw.push('\n');
+	}
+	None
+}
+
+fn legacy(content: &str, format: &Format, delimiter: &str) -> Result<(Header, Block)> {
+	let (open, close) = format.plugin().comment();
+	let mut block: Option<Block> = None;
+	let mut data = HashMap::new();
+	for (i, line) in content.lines().enumerate() {
+		let trimmed_line = line.trim();
+		let block = match &mut block {
+			Some(block) => block,
+			None => {
+				if trimmed_line == open {
+					block = Some(Block {
+						open: i,
+						close: None,
+						keys: Vec::new(),
+					});
+				}
+				continue;
+			}
+		};
+		if trimmed_line == close {
+			block.close = Some(i);
+			break;
+		}
+		if let Some((key, value)) = trimmed_line.split_once(delimiter) {
+			data.insert(key.trim().to_string(), value.trim().to_string());
+			block.keys.push(i);
+		}
+	}
+	let block = block.context("No header found")?;
+
+	for key in ["created", "root"] {
+		if !data.contains_key(key) {
+			bail!("missing required key: {}", key);
+		}
+	}
+	let list = |value: Option<String>| -> Vec<String> {
+		value
+			.map(|v| {
+				v.split(',')
+					.map(|i| i.trim().to_string())
+					.filter(|i| !i.is_empty())
+					.collect()
+			})
+			.unwrap_or_default()
+	};
+	let mut number = |key: &str| -> Result<Option<f64>> {
+		data.remove(key)
+			.map(|v| {
+				v.parse::<f64>()
+					.with_context(|| format!("{} is not a valid number for {}", v, key))
+			})
+			.transpose()
+	};
+	let score = number("score")?;
+	let out_of = number("out_of")?;
+	let weight = number("weight")?;
+	let header = Header {
+		created: date::parse(&data.remove("created").unwrap())?,
+		root: data.remove("root").unwrap(),
+		class: data.remove("class"),
+		doc_type: data
+			.remove("type")
+			.map(|t| {
+				DocumentType::from_str(&t).map_err(|_| {
+					anyhow!(
+						"{} is not a valid document type. Options are: {}",
+						t,
+						DocumentType::VARIANTS.join(", ")
+					)
+				})
+			})
+			.transpose()?,
+		bibliography: list(data.remove("bibliography"))
+			.into_iter()
+			.map(PathBuf::from)
+			.collect(),
+		csl: data.remove("csl").map(PathBuf::from),
+		metadata: Metadata {
+			due: data.remove("due").map(|d| date::parse(&d)).transpose()?,
+			tags: list(data.remove("tags")),
+			status: data
+				.remove("status")
+				.map(|s| {
+					Status::from_str(&s).map_err(|_| {
+						anyhow!(
+							"{} is not a valid status. Options are: {}",
+							s,
+							Status::VARIANTS.join(", ")
+						)
+					})
+				})
+				.transpose()?,
+			score,
+			out_of,
+			weight,
+			custom: data
+				.into_iter()
+				.map(|(key, value)| (key, Value::String(value)))
+				.collect(),
+		},
+	};
+	Ok((header, block))
+}
+
+pub mod date {
+	use anyhow::{Context, Result};
+	use chrono::NaiveDate;
+	use serde::{de, Deserialize, Deserializer};
+
+	#[derive(Deserialize)]
+	#[serde(untagged)]
+	enum Raw {
+		Text(String),
+		Toml(toml::value::Datetime),
+	}
+
+	pub fn parse(date: &str) -> Result<NaiveDate> {
+		NaiveDate::parse_from_str(date, "%F").context(format!(
+			"{} is not a valid date. Please use the YYYY-MM-DD format",
+			date
+		))
+	}
+
+	pub fn required<'de, D: Deserializer<'de>>(deserializer: D) -> Result<NaiveDate, D::Error> {
+		let date = match Raw::deserialize(deserializer)? {
+			Raw::Text(text) => text,
+			Raw::Toml(datetime) => datetime.to_string(),
+		};
+		parse(&date).map_err(de::Error::custom)
+	}
+
+	pub fn optional<'de, D: Deserializer<'de>>(
+		deserializer: D,
+	) -> Result<Option<NaiveDate>, D::Error> {
+		required(deserializer).map(Some)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use std::collections::BTreeMap;
+	use std::path::PathBuf;
+
+	use anyhow::Result;
+	use chrono::NaiveDate;
+	use serde_json::Value;
+
+	use crate::conf::Format;
+	use crate::header::{migrate, parse_due, update, Header, Metadata, Status};
+
+	fn header() -> Header {
+		Header {
+			created: NaiveDate::from_ymd_opt(2022, 10, 1).unwrap(),
+			root: String::from("base.tex"),
+			class: None,
+			doc_type: None,
+			bibliography: vec![PathBuf::from("refs.bib")],
+			csl: None,
+			metadata: Metadata {
+				due: Some(NaiveDate::from_ymd_opt(2022, 10, 7).unwrap()),
+				tags: vec![String::from("kinematics")],
+				status: None,
+				score: None,
+				out_of: None,
+				weight: None,
+				custom: BTreeMap::from([(String::from("partner"), Value::from("Richard"))]),
+			},
+		}
+	}
+
+	#[test]
+	fn parse() -> Result<()> {
+		assert_eq!(
+			Header::parse(
+				"---\ncreated: 2022-10-01\nroot: base.tex\nbibliography: [refs.bib]\ndue: \
+				 2022-10-07\ntags: [kinematics]\npartner: Richard\n---\n\n# Lab > Theory\n",
+				&Format::Markdown,
+				">"
+			)?,
+			header()
+		);
+		assert_eq!(
+			Header::parse(
+				"% +++\n% created = 2022-10-01\n% root = \"base.tex\"\n% bibliography = \
+				 [\"refs.bib\"]\n% due = 2022-10-07\n% tags = [\"kinematics\"]\n% partner = \
+				 \"Richard\"\n% +++\n\n$a > b$\n",
+				&Format::LaTeX,
+				">"
+			)?,
+			header()
+		);
+		assert_eq!(
+			Header::parse(
+				"<!--\ncreated > 2022-10-01\nroot > base.tex\nbibliography > refs.bib\ndue > \
+				 2022-10-07\ntags > kinematics\npartner > Richard\n-->\n\n# a > b\n",
+				&Format::Markdown,
+				">"
+			)?,
+			header()
+		);
+		Ok(())
+	}
+
+	#[test]
+	fn migrate_legacy() -> Result<()> {
+		assert_eq!(
+			migrate(
+				"\\iffalse\ncreated > 2022-10-01\nroot > base.tex\n\\fi\n\nHello\n",
+				&Format::LaTeX,
+				">"
+			)?,
+			Some(String::from(
+				"% +++\n% created = \"2022-10-01\"\n% root = \"base.tex\"\n% +++\n\nHello\n"
+			))
+		);
+		assert_eq!(
+			migrate(
+				"---\ncreated: 2022-10-01\nroot: base.tex\n---\n",
+				&Format::Markdown,
+				">"
+			)?,
+			None
+		);
+		assert_eq!(
+			migrate(
+				"..\n   created > 2022-10-01\n\n   root > base.rst\n..\n\nHello\n",
+				&Format::ReStructuredText,
+				">"
+			)?,
+			Some(String::from(
+				".. +++\n.. created = \"2022-10-01\"\n.. root = \"base.rst\"\n.. +++\n\nHello\n"
+			))
+		);
+		Ok(())
+	}
+
+	#[test]
+	fn update_status() -> Result<()> {
+		let set_status = |h: &mut Header| h.metadata.status = Some(Status::InProgress);
+		assert_eq!(
+			update(
+				"---\ncreated: 2022-10-01\nroot: base.tex\n---\n\n# Lab\n",
+				&Format::Markdown,
+				">",
+				set_status
+			)?,
+			"---\ncreated: 2022-10-01\nroot: base.tex\nstatus: in-progress\n---\n\n# Lab\n"
+		);
+		assert_eq!(
+			update(
+				"<!--\ncreated > 2022-10-01\nroot > base.tex\n-->\n\n# Lab\n",
+				&Format::Markdown,
+				">",
+				set_status
+			)?,
+			"<!--\ncreated > 2022-10-01\nroot > base.tex\nstatus > in-progress\n-->\n\n# Lab\n"
+		);
+		Ok(())
+	}
+
+	#[test]
+	fn due() -> Result<()> {
+		// A Wednesday
+		let today = NaiveDate::from_ymd_opt(2022, 10, 5).unwrap();
+		let date = |day| NaiveDate::from_ymd_opt(2022, 10, day).unwrap();
+		assert_eq!(parse_due("friday", today)?, date(7));
+		assert_eq!(parse_due("Wed", today)?, date(12));
+		assert_eq!(parse_due("+3d", today)?, date(8));
+		assert_eq!(parse_due("+1w", today)?, date(12));
+		assert_eq!(parse_due("2022-10-31", today)?, date(31));
+		assert!(parse_due("someday", today).is_err());
+		Ok(())
+	}
+}
@@ -0,0 +1,77 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::Result;
+use handlebars::Handlebars;
+use serde_json::json;
+
+use crate::branch::Branch;
+use crate::conf::Config;
+
+pub fn standalone(branch: &Branch, config: &Config, content: &str) -> Result<String> {
+	Ok(Handlebars::new().render_template(
+		include_str!("standalone.html"),
+		&json!({
+			"name": branch.name,
+			"author": config.author,
+			"class": {
+				"name": branch.class.name,
+				"teacher": branch.class.teacher,
+			},
+			"type": branch.doc_type.to_string(),
+			"date": branch.creation_time.format("%B %e, %Y").to_string(),
+			"content": content,
+		}),
+	)?)
+}
+
+pub fn inline_images(html: &str, imgs_dir: &Path) -> Result<String> {
+	let mut output = String::new();
+	let mut rest = html;
+	while let Some(start) = rest.find("src=\"") {
+		let (before, after) = rest.split_at(start + "src=\"".len());
+		output.push_str(before);
+		let end = after.find('"').unwrap_or(after.len());
+		let src = &after[..end];
+		output.push_str(&match data_uri(src, imgs_dir)? {
+			Some(uri) => uri,
+			None => src.to_string(),
+		});
+		rest = &after[end..];
+	}
+	output.push_str(rest);
+	Ok(output)
+}
+
+fn data_uri(src: &str, imgs_dir: &Path) -> Result<Option<String>> {
+	if src.contains("://") || src.starts_with("data:") {
+		return Ok(None);
+	}
+	let path = [imgs_dir.join(src), Path::new(src).to_path_buf()]
+		.into_iter()
+		.find(|p| p.is_file());
+	let path = match path {
+		Some(path) => path,
+		None => return Ok(None),
+	};
+	let mime = match path
+		.extension()
+		.unwrap_or_default()
+		.to_str()
+		.unwrap_or_default()
+		.to_lowercase()
+		.as_str()
+	{
+		"png" => "image/png",
+		"jpg" | "jpeg" => "image/jpeg",
+		"gif" => "image/gif",
+		"svg" => "image/svg+xml",
+		"webp" => "image/webp",
+		_ => return Ok(None),
+	};
+	Ok(Some(format!(
+		"data:{};base64,{}",
+		mime,
+		base64::encode(fs::read(path)?)
+	)))
+}
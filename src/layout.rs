@@ -0,0 +1,275 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::{bail, Context, Result};
+use chrono::{Datelike, Month, NaiveDate};
+use num_traits::FromPrimitive;
+use serde::{Deserialize, Serialize};
+
+use crate::conf::DocumentType;
+
+const LEGACY_BRANCH: &str = "{class}/{month}/{type}/{name}";
+const LEGACY_IMGS: &str = "{class}/{month}/{name}";
+const FIELDS: [&str; 6] = ["class", "type", "name", "year", "month", "day"];
+
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+#[serde(try_from = "String", into = "String")]
+pub struct Layout {
+	branch: String,
+	imgs: String,
+}
+
+pub struct Fields<'a> {
+	pub class: &'a str,
+	pub doc_type: &'a DocumentType,
+	pub name: &'a str,
+	pub created: NaiveDate,
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+	Literal(String),
+	Field(String, Option<usize>),
+}
+
+impl Layout {
+	pub fn branch(&self, fields: &Fields) -> PathBuf { render(&self.branch, fields) }
+
+	pub fn imgs(&self, fields: &Fields) -> PathBuf { render(&self.imgs, fields) }
+
+	pub fn matches(&self, path: &Path) -> Option<HashMap<String, String>> {
+		let path = path
+			.iter()
+			.map(|c| c.to_str())
+			.collect::<Option<Vec<&str>>>()?
+			.join("/");
+		let mut captures = HashMap::new();
+		if capture(&tokens(&self.branch).ok()?, &path, &mut captures) {
+			Some(captures)
+		} else {
+			None
+		}
+	}
+}
+
+impl Default for Layout {
+	fn default() -> Self {
+		Self {
+			branch: String::from(LEGACY_BRANCH),
+			imgs: String::from(LEGACY_IMGS),
+		}
+	}
+}
+
+impl FromStr for Layout {
+	type Err = anyhow::Error;
+
+	fn from_str(s: &str) -> Result<Self> {
+		let pattern = s.trim_matches('/');
+		let tokens = tokens(pattern)?;
+		if !tokens
+			.iter()
+			.any(|t| matches!(t, Token::Field(field, _) if field == "name"))
+		{
+			bail!("Layout {} must contain {{name}}", s);
+		}
+		if pattern
+			.split('/')
+			.any(|s| s.is_empty() || s == "." || s == "..")
+		{
+			bail!("Layout {} contains an empty, . or .. folder", s);
+		}
+		Ok(Self {
+			branch: pattern.to_string(),
+			imgs: pattern.to_string(),
+		})
+	}
+}
+
+impl TryFrom<String> for Layout {
+	type Error = anyhow::Error;
+
+	fn try_from(value: String) -> Result<Self> { Layout::from_str(&value) }
+}
+
+impl From<Layout> for String {
+	fn from(layout: Layout) -> Self { layout.to_string() }
+}
+
+impl fmt::Display for Layout {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { write!(f, "{}", self.branch) }
+}
+
+fn tokens(pattern: &str) -> Result<Vec<Token>> {
+	let mut tokens = Vec::new();
+	let mut rest = pattern;
+	while let Some(start) = rest.find('{') {
+		if start > 0 {
+			tokens.push(Token::Literal(rest[..start].to_string()));
+		}
+		let end = rest[start..]
+			.find('}')
+			.with_context(|| format!("Unclosed {{ in layout {}", pattern))?
+			+ start;
+		let (field, width) = match rest[start + 1..end].split_once(':') {
+			Some((field, width)) => {
+				(
+					field,
+					Some(width.parse::<usize>().with_context(|| {
+						format!("{} is not a valid width for {{{}}}", width, field)
+					})?),
+				)
+			}
+			None => (&rest[start + 1..end], None),
+		};
+		if !FIELDS.contains(&field) {
+			bail!(
+				"{{{}}} is not a valid layout field. Options are: {}",
+				field,
+				FIELDS.map(|f| format!("{{{}}}", f)).join(", ")
+			);
+		}
+		if width.is_some() && matches!(field, "class" | "type" | "name") {
+			bail!("{{{}}} can't have a width", field);
+		}
+		tokens.push(Token::Field(field.to_string(), width));
+		rest = &rest[end + 1..];
+	}
+	if !rest.is_empty() {
+		tokens.push(Token::Literal(rest.to_string()));
+	}
+	Ok(tokens)
+}
+
+fn render(pattern: &str, fields: &Fields) -> PathBuf {
+	let mut output = String::new();
+	for token in tokens(pattern).expect("Layout was validated when parsed") {
+		match token {
+			Token::Literal(literal) => output.push_str(&literal),
+			Token::Field(field, width) => {
+				let number = match field.as_str() {
+					"class" => {
+						output.push_str(fields.class);
+						continue;
+					}
+					"type" => {
+						output.push_str(&fields.doc_type.to_string());
+						continue;
+					}
+					"name" => {
+						output.push_str(fields.name);
+						continue;
+					}
+					"month" if width.is_none() => {
+						output.push_str(Month::from_u32(fields.created.month()).unwrap().name());
+						continue;
+					}
+					"year" => fields.created.year() as u32,
+					"month" => fields.created.month(),
+					_ => fields.created.day(),
+				};
+				output.push_str(&format!("{:0width$}", number, width = width.unwrap_or(0)));
+			}
+		}
+	}
+	output.split('/').collect()
+}
+
+fn capture(tokens: &[Token], path: &str, captures: &mut HashMap<String, String>) -> bool {
+	match tokens.split_first() {
+		None => path.is_empty(),
+		Some((Token::Literal(literal), rest)) => {
+			match path.strip_prefix(literal.as_str()) {
+				Some(path) => capture(rest, path, captures),
+				None => false,
+			}
+		}
+		Some((Token::Field(field, width), rest)) => {
+			for (end, _) in path.char_indices().skip(1).chain([(path.len(), ' ')]) {
+				let value = &path[..end];
+				if value.contains('/') {
+					break;
+				}
+				let valid = match field.as_str() {
+					"class" | "name" => true,
+					"type" => DocumentType::from_str(value).is_ok(),
+					"month" if width.is_none() => {
+						(1..=12).any(|m| Month::from_u32(m).unwrap().name() == value)
+					}
+					_ => value.chars().all(|c| c.is_ascii_digit()),
+				};
+				if valid && capture(rest, &path[end..], captures) {
+					captures.insert(field.clone(), value.to_string());
+					return true;
+				}
+			}
+			false
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use std::collections::HashMap;
+	use std::path::{Path, PathBuf};
+	use std::str::FromStr;
+
+	use chrono::NaiveDate;
+
+	use super::{Fields, Layout};
+	use crate::conf::DocumentType;
+
+	#[test]
+	fn render() -> anyhow::Result<()> {
+		let fields = Fields {
+			class: "AP Physics 2",
+			doc_type: &DocumentType::Lab,
+			name: "Circuits",
+			created: NaiveDate::from_ymd_opt(2022, 9, 6).unwrap(),
+		};
+		assert_eq!(
+			Layout::default().branch(&fields),
+			PathBuf::from("AP Physics 2/September/Lab/Circuits")
+		);
+		assert_eq!(
+			Layout::default().imgs(&fields),
+			PathBuf::from("AP Physics 2/September/Circuits")
+		);
+		let layout = Layout::from_str("{class}/{year}-{month:02}/{type}/{name}")?;
+		assert_eq!(
+			layout.branch(&fields),
+			PathBuf::from("AP Physics 2/2022-09/Lab/Circuits")
+		);
+		assert_eq!(layout.imgs(&fields), layout.branch(&fields));
+
+		assert!(Layout::from_str("{class}/{type}").is_err());
+		assert!(Layout::from_str("{class}/{week}/{name}").is_err());
+		assert!(Layout::from_str("{class}//{name}").is_err());
+		Ok(())
+	}
+
+	#[test]
+	fn matches() -> anyhow::Result<()> {
+		let layout = Layout::from_str("{class}/{year}-{month:02}/{type}/{name}")?;
+		assert_eq!(
+			layout.matches(Path::new("AP Physics 2/2022-09/Lab/Circuits - Part 1")),
+			Some(HashMap::from([
+				(String::from("class"), String::from("AP Physics 2")),
+				(String::from("year"), String::from("2022")),
+				(String::from("month"), String::from("09")),
+				(String::from("type"), String::from("Lab")),
+				(String::from("name"), String::from("Circuits - Part 1")),
+			]))
+		);
+		assert_eq!(
+			layout.matches(Path::new("AP Physics 2/September/Lab/Circuits")),
+			None
+		);
+		assert!(Layout::default()
+			.matches(Path::new("AP Physics 2/September/Lab/Circuits"))
+			.is_some());
+		Ok(())
+	}
+}
@@ -1,9 +1,20 @@
+use std::env;
+use std::path::{Component, Path, PathBuf};
+use std::sync::OnceLock;
+
+use anyhow::{Context, Result};
+
+use crate::error::{Error, Kind};
+
 pub mod folders {
 	pub const BRANCHES: &str = "docs";
 	pub const PDFS: &str = "pdfs";
+	pub const HTML: &str = "html";
+	pub const DOCX: &str = "docx";
 	pub const IMAGES: &str = "imgs";
 	pub const TEMPLATES: &str = "templates";
 	pub const BUILD: &str = ".build";
+	pub const ARCHIVE: &str = "archive";
 
 	pub const BRANCH_TEMPLATES: &str = "branch";
 	pub const ROOT_TEMPLATES: &str = "root";
@@ -11,7 +22,64 @@ pub mod folders {
 
 pub mod files {
 	pub const CONFIG: &str = "optic.toml";
+	pub const GLOBAL_CONFIG: &str = "config.toml";
 	pub const LATEX_BUILD: &str = "main.tex";
+	pub const TYPST_BUILD: &str = "main.typ";
 	pub const PDF_BUILD: &str = "main.pdf";
+	pub const LOG_BUILD: &str = "main.log";
+	pub const AUX_BUILD: &str = "main.aux";
 	pub const FAIL_LOG: &str = "failure.log";
 }
+
+static INVOKED_FROM: OnceLock<PathBuf> = OnceLock::new();
+
+pub fn enter_root() -> Result<()> {
+	let cwd = env::current_dir().context("Failed to get current directory")?;
+	let root = cwd
+		.ancestors()
+		.find(|dir| dir.join(files::CONFIG).is_file())
+		.with_context(|| {
+			Error::new(
+				Kind::Config,
+				format!(
+					"No {} in {} or any parent folder",
+					files::CONFIG,
+					cwd.display()
+				),
+			)
+			.hint("run optic from inside an optic project")
+		})?;
+	env::set_current_dir(root).context("Failed to change to project root")?;
+	INVOKED_FROM.get_or_init(|| cwd.strip_prefix(root).unwrap().to_path_buf());
+	Ok(())
+}
+
+// Paths given on the command line are relative to where optic was run, not the project root
+pub fn from_invocation(path: &Path) -> PathBuf {
+	let joined = match INVOKED_FROM.get() {
+		Some(dir) if path.is_relative() => dir.join(path),
+		_ => return path.to_path_buf(),
+	};
+	let mut normalized = PathBuf::new();
+	for component in joined.components() {
+		match component {
+			Component::CurDir => {}
+			Component::ParentDir if normalized.file_name().is_some() => {
+				normalized.pop();
+			}
+			_ => normalized.push(component),
+		}
+	}
+	normalized
+}
+
+pub fn global_config() -> Option<PathBuf> {
+	let config_dir = match env::var_os("XDG_CONFIG_HOME") {
+		Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+		_ => {
+			PathBuf::from(env::var_os("HOME").or_else(|| env::var_os("USERPROFILE"))?)
+				.join(".config")
+		}
+	};
+	Some(config_dir.join("optic").join(files::GLOBAL_CONFIG))
+}
@@ -1,23 +1,53 @@
 mod branch;
+mod calendar;
+mod classes;
 mod cli;
 mod cmd;
 mod conf;
+mod diagnostic;
+mod error;
+mod format;
+mod grades;
+mod header;
+mod html;
+mod layout;
 mod locations;
+mod markdown;
+mod selector;
+mod starter;
 mod template;
+mod validate;
+
+use std::process;
 
 fn main() {
 	let matches = cli::setup().get_matches();
-	match matches.subcommand() {
-		Some(("new", _)) => cmd::new::run(),
+	let result = match matches.subcommand() {
+		Some(("init", args)) => cmd::init::run(args),
+		Some(("new", args)) => cmd::new::run(args),
 		Some(("build", args)) => cmd::build::run(args),
 		Some(("watch", args)) => cmd::watch::run(args),
-		Some(("open", _)) => cmd::open::run(),
-		Some(("reveal", _)) => cmd::reveal::run(),
+		Some(("open", args)) => cmd::open::run(args),
+		Some(("reveal", args)) => cmd::reveal::run(args),
 		Some(("check", _)) => cmd::check::run(),
-		Some(("trash", _)) => cmd::trash::run(),
+		Some(("trash", args)) => cmd::trash::run(args),
 		Some(("completion", args)) => cmd::completion::run(args),
+		Some(("failures", _)) => cmd::failures::run(),
 		Some(("commit", _)) => cmd::commit::run(),
 		Some(("search", _)) => cmd::search::run(),
+		Some(("agenda", _)) => cmd::agenda::run(),
+		Some(("calendar", args)) => cmd::calendar::run(args),
+		Some(("grades", args)) => cmd::grades::run(args),
+		Some(("migrate-headers", _)) => cmd::migrate_headers::run(),
+		Some(("relayout", args)) => cmd::relayout::run(args),
+		Some(("archive", args)) => cmd::archive::run(args),
+		Some(("config", args)) => cmd::config::run(args),
+		Some(("class", args)) => cmd::class::run(args),
+		Some(("mv", args)) => cmd::mv::run(args),
 		_ => unreachable!(),
+	};
+	if let Err(err) = result {
+		error::report(&err);
+		process::exit(error::code(&err));
 	}
 }
@@ -0,0 +1,392 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use pulldown_cmark::{html, Alignment, Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+
+fn options() -> Options {
+	Options::ENABLE_TABLES
+		| Options::ENABLE_FOOTNOTES
+		| Options::ENABLE_TASKLISTS
+		| Options::ENABLE_MATH
+		| Options::ENABLE_YAML_STYLE_METADATA_BLOCKS
+}
+
+pub fn to_html(content: &str) -> String {
+	let events = Parser::new_ext(content, options()).map(|event| {
+		match event {
+			Event::InlineMath(math) => {
+				Event::InlineHtml(
+					format!(
+						"<span class=\"math inline\">\\({}\\)</span>",
+						escape_html(&math)
+					)
+					.into(),
+				)
+			}
+			Event::DisplayMath(math) => {
+				Event::InlineHtml(
+					format!(
+						"<span class=\"math display\">\\[{}\\]</span>",
+						escape_html(&math)
+					)
+					.into(),
+				)
+			}
+			event => event,
+		}
+	});
+	let mut output = String::new();
+	html::push_html(&mut output, events);
+	output
+}
+
+pub fn to_latex(content: &str, imgs_dir: &Path) -> String {
+	let mut events = Vec::new();
+	let mut footnotes = HashMap::new();
+	let mut footnote: Option<(String, Vec<Event>)> = None;
+	let mut in_metadata = false;
+	for event in Parser::new_ext(content, options()) {
+		match event {
+			Event::Start(Tag::MetadataBlock(_)) => in_metadata = true,
+			Event::End(TagEnd::MetadataBlock(_)) => in_metadata = false,
+			_ if in_metadata => (),
+			Event::Start(Tag::FootnoteDefinition(label)) => {
+				footnote = Some((label.to_string(), Vec::new()))
+			}
+			Event::End(TagEnd::FootnoteDefinition) => {
+				if let Some((label, events)) = footnote.take() {
+					footnotes.insert(label, events);
+				}
+			}
+			event => {
+				match &mut footnote {
+					Some((_, events)) => events.push(event),
+					None => events.push(event),
+				}
+			}
+		}
+	}
+
+	let mut renderer = Renderer {
+		imgs_dir,
+		footnotes: &footnotes,
+		output: String::new(),
+		in_code_block: false,
+		in_image: false,
+		cell: 0,
+		expanding: HashSet::new(),
+	};
+	renderer.render(events);
+	renderer.output.trim_end().to_string() + "\n"
+}
+
+struct Renderer<'a> {
+	imgs_dir: &'a Path,
+	footnotes: &'a HashMap<String, Vec<Event<'a>>>,
+	output: String,
+	in_code_block: bool,
+	in_image: bool,
+	cell: usize,
+	// Footnotes being rendered right now, so one that refers back to itself isn't expanded forever
+	expanding: HashSet<String>,
+}
+
+impl<'a> Renderer<'a> {
+	fn render(&mut self, events: Vec<Event<'a>>) {
+		for event in events {
+			if self.in_image {
+				if let Event::End(TagEnd::Image) = event {
+					self.in_image = false;
+				}
+				continue;
+			}
+			match event {
+				Event::Start(tag) => self.start(tag),
+				Event::End(tag) => self.end(tag),
+				Event::Text(text) => {
+					if self.in_code_block {
+						self.output.push_str(&text);
+					} else {
+						self.output.push_str(&escape(&text));
+					}
+				}
+				Event::Code(code) => {
+					self.output
+						.push_str(&format!("\\texttt{{{}}}", escape_verbatim(&code)))
+				}
+				Event::InlineMath(math) => self.output.push_str(&format!("${}$", math)),
+				Event::DisplayMath(math) => self.output.push_str(&format!("\\[{}\\]", math)),
+				Event::FootnoteReference(label) if self.expanding.contains(label.as_ref()) => (),
+				Event::FootnoteReference(label) => {
+					let mut expanding = self.expanding.clone();
+					expanding.insert(label.to_string());
+					let mut renderer = Renderer {
+						imgs_dir: self.imgs_dir,
+						footnotes: self.footnotes,
+						output: String::new(),
+						in_code_block: false,
+						in_image: false,
+						cell: 0,
+						expanding,
+					};
+					if let Some(events) = self.footnotes.get(label.as_ref()) {
+						renderer.render(events.clone());
+					}
+					self.output
+						.push_str(&format!("\\footnote{{{}}}", renderer.output.trim()));
+				}
+				Event::SoftBreak => self.output.push('\n'),
+				Event::HardBreak => self.output.push_str("\\\\\n"),
+				Event::Rule => {
+					self.output
+						.push_str("\\begin{center}\\rule{0.5\\linewidth}{0.5pt}\\end{center}\n\n")
+				}
+				Event::TaskListMarker(checked) => {
+					if self.output.ends_with("\\item ") {
+						self.output.truncate(self.output.len() - "\\item ".len());
+					}
+					self.output.push_str(if checked {
+						"\\item[{[x]}] "
+					} else {
+						"\\item[{[ ]}] "
+					});
+				}
+				Event::Html(_) | Event::InlineHtml(_) => (),
+			}
+		}
+	}
+
+	fn start(&mut self, tag: Tag<'a>) {
+		match tag {
+			Tag::Heading { level, .. } => {
+				self.output.push_str(match level {
+					HeadingLevel::H1 => "\\section{",
+					HeadingLevel::H2 => "\\subsection{",
+					HeadingLevel::H3 => "\\subsubsection{",
+					HeadingLevel::H4 => "\\paragraph{",
+					_ => "\\subparagraph{",
+				})
+			}
+			Tag::BlockQuote(_) => self.output.push_str("\\begin{quote}\n"),
+			Tag::CodeBlock(_) => {
+				self.in_code_block = true;
+				self.output.push_str("\\begin{verbatim}\n");
+			}
+			Tag::List(None) => self.output.push_str("\\begin{itemize}\n"),
+			Tag::List(Some(start)) => {
+				self.output.push_str("\\begin{enumerate}\n");
+				if start != 1 {
+					self.output
+						.push_str(&format!("\\setcounter{{enumi}}{{{}}}\n", start - 1));
+				}
+			}
+			Tag::Item => self.output.push_str("\\item "),
+			Tag::Table(alignments) => {
+				let columns: String = alignments
+					.iter()
+					.map(|a| {
+						match a {
+							Alignment::Center => 'c',
+							Alignment::Right => 'r',
+							Alignment::Left | Alignment::None => 'l',
+						}
+					})
+					.collect();
+				self.output.push_str(&format!(
+					"\\begin{{longtable}}[]{{@{{}}{}@{{}}}}\n\\toprule\n",
+					columns
+				));
+			}
+			Tag::TableHead | Tag::TableRow => self.cell = 0,
+			Tag::TableCell => {
+				if self.cell > 0 {
+					self.output.push_str(" & ");
+				}
+				self.cell += 1;
+			}
+			Tag::Emphasis => self.output.push_str("\\emph{"),
+			Tag::Strong => self.output.push_str("\\textbf{"),
+			Tag::Link { dest_url, .. } => {
+				self.output
+					.push_str(&format!("\\href{{{}}}{{", escape_url(&dest_url)))
+			}
+			Tag::Image { dest_url, .. } => {
+				let url = dest_url.to_string();
+				let path = if url.contains("://") || Path::new(&url).is_absolute() {
+					url
+				} else {
+					self.imgs_dir.join(url).to_str().unwrap().to_string()
+				};
+				self.output
+					.push_str(&format!("\\includegraphics{{{}}}", path));
+				self.in_image = true;
+			}
+			_ => (),
+		}
+	}
+
+	fn end(&mut self, tag: TagEnd) {
+		match tag {
+			TagEnd::Paragraph => self.output.push_str("\n\n"),
+			TagEnd::Heading(_) => self.output.push_str("}\n\n"),
+			TagEnd::BlockQuote(_) => self.output.push_str("\\end{quote}\n\n"),
+			TagEnd::CodeBlock => {
+				self.in_code_block = false;
+				if !self.output.ends_with('\n') {
+					self.output.push('\n');
+				}
+				self.output.push_str("\\end{verbatim}\n\n");
+			}
+			TagEnd::List(ordered) => {
+				self.output.push_str(if ordered {
+					"\\end{enumerate}\n\n"
+				} else {
+					"\\end{itemize}\n\n"
+				})
+			}
+			TagEnd::Item => {
+				let trimmed = self.output.trim_end().len();
+				self.output.truncate(trimmed);
+				self.output.push('\n');
+			}
+			TagEnd::Table => self.output.push_str("\\bottomrule\n\\end{longtable}\n\n"),
+			TagEnd::TableHead => self.output.push_str(" \\\\\n\\midrule\n\\endhead\n"),
+			TagEnd::TableRow => self.output.push_str(" \\\\\n"),
+			TagEnd::Emphasis | TagEnd::Strong | TagEnd::Link => self.output.push('}'),
+			_ => (),
+		}
+	}
+}
+
+fn escape(text: &str) -> String {
+	let chars: Vec<char> = text.chars().collect();
+	let mut output = String::new();
+	let mut i = 0;
+	while i < chars.len() {
+		let c = chars[i];
+		if c == '\\' && chars.get(i + 1).is_some_and(|n| n.is_ascii_alphabetic()) {
+			// Pass raw LaTeX commands and their arguments through untouched
+			output.push(c);
+			i += 1;
+			while i < chars.len() && chars[i].is_ascii_alphabetic() {
+				output.push(chars[i]);
+				i += 1;
+			}
+			while let Some(open @ ('{' | '[')) = chars.get(i).copied() {
+				let close = if open == '{' { '}' } else { ']' };
+				let mut depth = 0;
+				while i < chars.len() {
+					output.push(chars[i]);
+					if chars[i] == open {
+						depth += 1;
+					} else if chars[i] == close {
+						depth -= 1;
+					}
+					i += 1;
+					if depth == 0 {
+						break;
+					}
+				}
+			}
+			continue;
+		}
+		match c {
+			'&' | '%' | '$' | '#' | '_' | '{' | '}' => {
+				output.push('\\');
+				output.push(c);
+			}
+			'~' => output.push_str("\\textasciitilde{}"),
+			'^' => output.push_str("\\textasciicircum{}"),
+			'\\' => output.push_str("\\textbackslash{}"),
+			_ => output.push(c),
+		}
+		i += 1;
+	}
+	output
+}
+
+fn escape_verbatim(text: &str) -> String {
+	let mut output = String::new();
+	for c in text.chars() {
+		match c {
+			'\\' => output.push_str("\\textbackslash{}"),
+			'~' => output.push_str("\\textasciitilde{}"),
+			'^' => output.push_str("\\textasciicircum{}"),
+			'&' | '%' | '$' | '#' | '_' | '{' | '}' => {
+				output.push('\\');
+				output.push(c);
+			}
+			_ => output.push(c),
+		}
+	}
+	output
+}
+
+fn escape_html(text: &str) -> String {
+	text.replace('&', "&amp;")
+		.replace('<', "&lt;")
+		.replace('>', "&gt;")
+}
+
+fn escape_url(url: &str) -> String { url.replace('%', "\\%").replace('#', "\\#") }
+
+#[cfg(test)]
+mod test {
+	use std::path::Path;
+
+	use crate::markdown::{to_html, to_latex};
+
+	#[test]
+	fn convert() {
+		let imgs = Path::new("../imgs/Physics/October/Lab one");
+		assert_eq!(
+			to_latex(
+				"<!--\ncreated > 2022-10-01\n-->\n\n# Hello *world*\n\nCost is 5% & $x^2$ \
+				 \\textbf{raw}.\n",
+				imgs
+			),
+			"\\section{Hello \\emph{world}}\n\nCost is 5\\% \\& $x^2$ \\textbf{raw}.\n"
+		);
+		assert_eq!(
+			to_latex("- [x] done\n- [ ] todo\n", imgs),
+			"\\begin{itemize}\n\\item[{[x]}] done\n\\item[{[ ]}] todo\n\\end{itemize}\n"
+		);
+		assert_eq!(
+			to_latex("| a | b |\n|:--|--:|\n| 1 | 2 |\n", imgs),
+			"\\begin{longtable}[]{@{}lr@{}}\n\\toprule\na & b \\\\\n\\midrule\n\\endhead\n1 & 2 \
+			 \\\\\n\\bottomrule\n\\end{longtable}\n"
+		);
+		assert_eq!(
+			to_latex("Note[^1]\n\n[^1]: A *footnote*.\n", imgs),
+			"Note\\footnote{A \\emph{footnote}.}\n"
+		);
+		assert_eq!(
+			to_latex("Note[^a]\n\n[^a]: See [^a].\n", imgs),
+			"Note\\footnote{See .}\n"
+		);
+		assert_eq!(
+			to_latex("Note[^a]\n\n[^a]: See [^b].\n\n[^b]: And [^a].\n", imgs),
+			"Note\\footnote{See \\footnote{And .}.}\n"
+		);
+		assert_eq!(
+			to_latex("![Graph](graph.png)\n\n$$\nE = mc^2\n$$\n", imgs),
+			"\\includegraphics{../imgs/Physics/October/Lab one/graph.png}\n\n\\[\nE = mc^2\n\\]\n"
+		);
+		assert_eq!(
+			to_latex("```rust\nfn main() {}\n```\n", imgs),
+			"\\begin{verbatim}\nfn main() {}\n\\end{verbatim}\n"
+		);
+		assert_eq!(
+			to_latex("---\ncreated: 2022-10-01\n---\n\nHello\n", imgs),
+			"Hello\n"
+		);
+	}
+
+	#[test]
+	fn convert_html() {
+		assert_eq!(
+			to_html("# Energy\n\nIt is $E < mc^2$.\n"),
+			"<h1>Energy</h1>\n<p>It is <span class=\"math inline\">\\(E &lt; mc^2\\)</span>.</p>\n"
+		);
+	}
+}
@@ -0,0 +1,164 @@
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{Datelike, Month, NaiveDate};
+use clap::{Arg, ArgMatches, Command};
+use dialoguer::theme::ColorfulTheme;
+use dialoguer::FuzzySelect;
+use num_traits::FromPrimitive;
+use strum::VariantNames;
+
+use crate::branch::Branch;
+use crate::conf::{Config, DocumentType};
+use crate::locations;
+
+pub fn args(cmd: Command<'static>) -> Command<'static> {
+	cmd.arg(
+		Arg::new("branch")
+			.help("Path or name of the branch (defaults to the most recently modified)")
+			.takes_value(true),
+	)
+	.arg(
+		Arg::new("class")
+			.long("class")
+			.help("Only consider branches from this class")
+			.takes_value(true),
+	)
+	.arg(
+		Arg::new("type")
+			.long("type")
+			.help("Only consider branches of this document type")
+			.takes_value(true),
+	)
+	.arg(
+		Arg::new("month")
+			.long("month")
+			.help("Only consider branches created in this month (e.g. October or 10)")
+			.takes_value(true),
+	)
+	.arg(
+		Arg::new("since")
+			.long("since")
+			.help("Only consider branches created on or after this date (YYYY-MM-DD)")
+			.takes_value(true),
+	)
+	.arg(
+		Arg::new("until")
+			.long("until")
+			.help("Only consider branches created on or before this date (YYYY-MM-DD)")
+			.takes_value(true),
+	)
+	.arg(
+		Arg::new("term")
+			.long("term")
+			.help("Only consider branches from this term, including archived ones")
+			.takes_value(true),
+	)
+	.arg(
+		Arg::new("pick")
+			.long("pick")
+			.help("Pick the branch with a fuzzy finder")
+			.takes_value(false),
+	)
+}
+
+pub fn select(config: &Config, args: &ArgMatches) -> Result<Branch> {
+	let mut branches = branches(config, args)?;
+
+	if let Some(query) = args.value_of("branch") {
+		let path = locations::from_invocation(Path::new(query));
+		branches.retain(|b| b.path == path || b.name == query);
+		if branches.is_empty() {
+			bail!("No branch found with the path or name {}", query);
+		}
+	}
+	if branches.is_empty() {
+		bail!("No branches match the given filters");
+	}
+
+	let index = if args.is_present("pick") {
+		pick(&branches)?
+	} else {
+		0
+	};
+	Ok(branches.swap_remove(index))
+}
+
+// Archived branches can only be reached by naming their term
+pub fn branches(config: &Config, args: &ArgMatches) -> Result<Vec<Branch>> {
+	let branches = if args.is_present("term") {
+		Branch::get_all_with_archive(config)
+	} else {
+		Branch::get_all(config)
+	};
+	filter(config, branches?, args)
+}
+
+fn filter(config: &Config, branches: Vec<Branch>, args: &ArgMatches) -> Result<Vec<Branch>> {
+	let doc_type = match args.value_of("type") {
+		Some(doc_type) => {
+			Some(DocumentType::from_str(doc_type).map_err(|_| {
+				anyhow!(
+					"{} is not a valid document type. Options are: {}",
+					doc_type,
+					DocumentType::VARIANTS.join(", ")
+				)
+			})?)
+		}
+		None => None,
+	};
+	let month = match args.value_of("month") {
+		Some(month) => Some(parse_month(month)?),
+		None => None,
+	};
+	let since = match args.value_of("since") {
+		Some(date) => Some(parse_date(date)?),
+		None => None,
+	};
+	let until = match args.value_of("until") {
+		Some(date) => Some(parse_date(date)?),
+		None => None,
+	};
+	let class = args.value_of("class");
+	let term = args.value_of("term").map(|t| config.term(t)).transpose()?;
+
+	Ok(branches
+		.into_iter()
+		.filter(|b| class.is_none_or(|c| b.class.name == c))
+		.filter(|b| doc_type.as_ref().is_none_or(|t| b.doc_type == *t))
+		.filter(|b| month.is_none_or(|m| b.creation_time.month() == m.number_from_month()))
+		.filter(|b| since.is_none_or(|d| b.creation_time >= d))
+		.filter(|b| until.is_none_or(|d| b.creation_time <= d))
+		.filter(|b| term.is_none_or(|t| b.in_term(t)))
+		.collect())
+}
+
+pub fn pick(branches: &[Branch]) -> Result<usize> {
+	Ok(FuzzySelect::with_theme(&ColorfulTheme::default())
+		.with_prompt("Branch")
+		.items(branches)
+		.default(0)
+		.interact()?)
+}
+
+fn parse_month(month: &str) -> Result<Month> {
+	if let Ok(number) = month.parse::<u32>() {
+		return Month::from_u32(number).context(format!("{} is not a valid month number", number));
+	}
+	(1..=12)
+		.filter_map(Month::from_u32)
+		.find(|m| {
+			let name = m.name().to_lowercase();
+			let query = month.to_lowercase();
+			name == query || (query.len() >= 3 && name.starts_with(&query))
+		})
+		.context(format!("{} is not a valid month", month))
+}
+
+fn parse_date(date: &str) -> Result<NaiveDate> {
+	NaiveDate::parse_from_str(date, "%F").context(format!(
+		"{} is not a valid date. Please use the YYYY-MM-DD format",
+		date
+	))
+}
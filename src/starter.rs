@@ -0,0 +1,90 @@
+use anyhow::Result;
+use handlebars::Handlebars;
+use serde_json::json;
+use toml::Value;
+
+use crate::conf::Format;
+use crate::locations::folders;
+
+pub const FORMATS: [&str; 2] = ["LaTeX", "Markdown"];
+
+pub const TEMPLATES: [(&str, &str, &str); 5] = [
+	(
+		folders::BRANCH_TEMPLATES,
+		"default.tex.hbs",
+		include_str!("starter/branch/default.tex.hbs"),
+	),
+	(
+		folders::BRANCH_TEMPLATES,
+		"lab.tex.hbs",
+		include_str!("starter/branch/lab.tex.hbs"),
+	),
+	(
+		folders::BRANCH_TEMPLATES,
+		"default.md.hbs",
+		include_str!("starter/branch/default.md.hbs"),
+	),
+	(
+		folders::BRANCH_TEMPLATES,
+		"lab.md.hbs",
+		include_str!("starter/branch/lab.md.hbs"),
+	),
+	(
+		folders::ROOT_TEMPLATES,
+		"default.tex.hbs",
+		include_str!("starter/root/default.tex.hbs"),
+	),
+];
+
+pub const GITIGNORE: &str = ".build/";
+
+pub fn config(author: &str, format: &Format, classes: &[(String, String)]) -> Result<String> {
+	let mut reg = Handlebars::new();
+	reg.register_escape_fn(handlebars::no_escape);
+	// Values are rendered as TOML strings so quotes and backslashes stay valid
+	let quote = |s: &str| Value::String(s.to_string()).to_string();
+	Ok(reg.render_template(
+		include_str!("starter/optic.toml.hbs"),
+		&json!({
+			"author": quote(author),
+			"format": quote(&format.to_string()),
+			"classes": classes
+				.iter()
+				.map(|(name, teacher)| json!({"name": quote(name), "teacher": quote(teacher)}))
+				.collect::<Vec<_>>(),
+		}),
+	)?)
+}
+
+#[cfg(test)]
+mod test {
+	use super::config;
+	use crate::conf::{Config, Format};
+
+	#[test]
+	fn starter_config() -> anyhow::Result<()> {
+		let empty = toml::from_str::<Config>(&config("Matt Gleich", &Format::LaTeX, &[])?)?;
+		assert_eq!(empty.author, "Matt Gleich");
+		assert_eq!(empty.default_format, Format::LaTeX);
+		assert!(empty.classes.is_empty());
+
+		let config = toml::from_str::<Config>(&config(
+			"Matt \"Kiwi\" Gleich",
+			&Format::Markdown,
+			&[
+				(String::from("AP Physics 2"), String::from("Mr. Feynman")),
+				(String::from("English"), String::from("Ms. Woolf")),
+			],
+		)?)?;
+		assert_eq!(config.author, "Matt \"Kiwi\" Gleich");
+		assert_eq!(
+			config
+				.classes
+				.iter()
+				.map(|c| (c.name.as_str(), c.teacher.as_str()))
+				.collect::<Vec<_>>(),
+			vec![("AP Physics 2", "Mr. Feynman"), ("English", "Ms. Woolf")]
+		);
+		Ok(())
+	}
+}
@@ -3,6 +3,7 @@ use std::fs;
 use std::path::{Path, PathBuf};
 
 use anyhow::{bail, Result};
+use strum::IntoEnumIterator;
 
 use crate::conf::Format;
 use crate::locations::folders;
@@ -14,7 +15,7 @@ pub struct BranchTemplate {
 	pub format: Format,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct RootTemplate {
 	pub path: PathBuf,
 	pub name: String,
@@ -22,18 +23,16 @@ pub struct RootTemplate {
 
 impl BranchTemplate {
 	pub fn new(path: PathBuf) -> Result<Self> {
-		let latex_extension = ".tex.hbs";
-		let markdown_extension = ".md.hbs";
 		let filename = path.file_name().unwrap().to_str().unwrap();
-		let (format, extension) = if filename.ends_with(latex_extension) {
-			(Format::LaTeX, latex_extension)
-		} else if filename.ends_with(markdown_extension) {
-			(Format::Markdown, markdown_extension)
-		} else {
-			bail!("Improper file format for {}", path.display())
+		let (format, name) = match Format::iter().find_map(|f| {
+			let name = filename.strip_suffix(&f.plugin().template_suffix())?;
+			Some((f, name))
+		}) {
+			Some(found) => found,
+			None => bail!("Improper file format for {}", path.display()),
 		};
 		Ok(Self {
-			name: filename.strip_suffix(extension).unwrap().to_string(),
+			name: name.to_string(),
 			format,
 			path,
 		})
@@ -42,7 +41,6 @@ impl BranchTemplate {
 	pub fn get_all() -> Result<Vec<Self>> {
 		let files: Vec<PathBuf> =
 			fs::read_dir(Path::new(folders::TEMPLATES).join(folders::BRANCH_TEMPLATES))?
-				.into_iter()
 				.filter(|r| r.is_ok())
 				.map(|r| r.unwrap().path())
 				.filter(|r| r.is_file())
@@ -85,7 +83,6 @@ impl RootTemplate {
 	pub fn get_all() -> Result<Vec<Self>> {
 		let files: Vec<PathBuf> =
 			fs::read_dir(Path::new(folders::TEMPLATES).join(folders::ROOT_TEMPLATES))?
-				.into_iter()
 				.filter(|r| r.is_ok())
 				.map(|r| r.unwrap().path())
 				.filter(|r| r.is_file())
@@ -0,0 +1,485 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::Result;
+use serde::Deserialize;
+use strum::VariantNames;
+use toml::value::Table;
+use toml::Spanned;
+
+use crate::branch::Branch;
+use crate::conf::{Config, DocumentType, Format, Layers, Term};
+use crate::error;
+use crate::locations::{self, files, folders};
+use crate::template::{BranchTemplate, RootTemplate};
+
+#[derive(Debug, PartialEq)]
+pub struct Problem {
+	pub file: PathBuf,
+	pub position: Option<(usize, usize)>,
+	pub message: String,
+	pub fix: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+struct Spans {
+	#[serde(default)]
+	classes: Vec<ClassSpans>,
+	default_format: Option<Spanned<String>>,
+	view_with: Option<Spanned<Vec<String>>>,
+	open_with: Option<Spanned<Vec<String>>>,
+	latexmk: Option<Spanned<bool>>,
+	#[serde(default)]
+	terms: Vec<TermSpans>,
+}
+
+#[derive(Deserialize)]
+struct TermSpans {
+	name: Spanned<String>,
+}
+
+#[derive(Deserialize)]
+struct ClassSpans {
+	name: Spanned<String>,
+	#[serde(default)]
+	terms: Vec<Spanned<String>>,
+	format: Option<Spanned<String>>,
+	branch_template: Option<Spanned<String>>,
+	root_template: Option<Spanned<String>>,
+}
+
+impl Problem {
+	fn new<M: Into<String>>(file: &Path, message: M) -> Self {
+		Self {
+			file: file.to_path_buf(),
+			position: None,
+			message: message.into(),
+			fix: None,
+		}
+	}
+
+	fn at(mut self, content: &str, offset: usize) -> Self {
+		let before = &content[..offset.min(content.len())];
+		let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+		self.position = Some((
+			before.matches('\n').count() + 1,
+			before[line_start..].chars().count() + 1,
+		));
+		self
+	}
+
+	fn fix<F: Into<String>>(mut self, fix: F) -> Self {
+		self.fix = Some(fix.into());
+		self
+	}
+
+	fn toml(file: &Path, err: &toml::de::Error) -> Self {
+		let message = err.to_string();
+		let mut problem = Self::new(
+			file,
+			message
+				.split_once(" at line ")
+				.map_or(message.as_str(), |(m, _)| m),
+		);
+		problem.position = err.line_col().map(|(line, col)| (line + 1, col + 1));
+		problem
+	}
+}
+
+pub fn check() -> Result<Vec<Problem>> {
+	locations::enter_root()?;
+	let project = Path::new(files::CONFIG);
+	let global = locations::global_config().filter(|p| p.is_file());
+
+	let mut problems = Vec::new();
+	for file in [Some(project), global.as_deref()].into_iter().flatten() {
+		if let Err(err) = toml::from_str::<Table>(&fs::read_to_string(file)?) {
+			problems.push(Problem::toml(file, &err));
+		}
+	}
+	if !problems.is_empty() {
+		return Ok(problems);
+	}
+
+	let layers = Layers::read()?;
+	let config = match layers.config() {
+		Ok(config) => config,
+		Err(err) => {
+			// Parsing optic.toml alone gives a position when the problem is in it
+			problems.push(match toml::from_str::<Config>(&layers.content) {
+				Err(err) => Problem::toml(project, &err),
+				Ok(_) => Problem::new(global.as_deref().unwrap_or(project), format!("{:#}", err)),
+			});
+			return Ok(problems);
+		}
+	};
+
+	problems.extend(config_problems(&layers.content, &config));
+	problems.extend(template_problems(&layers.content, &config));
+	problems.extend(branch_problems(&config)?);
+	Ok(problems)
+}
+
+fn config_problems(content: &str, config: &Config) -> Vec<Problem> {
+	let file = Path::new(files::CONFIG);
+	let spans: Spans = toml::from_str(content).unwrap_or_default();
+	let mut problems = Vec::new();
+
+	let mut names = HashSet::new();
+	for class in &spans.classes {
+		if !names.insert(class.name.get_ref()) {
+			problems.push(
+				Problem::new(
+					file,
+					format!("{} is defined more than once", class.name.get_ref()),
+				)
+				.at(content, class.name.start())
+				.fix("rename or remove one of the [[classes]] entries with this name"),
+			);
+		}
+		for term in &class.terms {
+			if !config.terms.iter().any(|t| &t.name == term.get_ref()) {
+				problems.push(
+					Problem::new(
+						file,
+						format!(
+							"{} uses the term {} which isn't defined",
+							class.name.get_ref(),
+							term.get_ref()
+						),
+					)
+					.at(content, term.start())
+					.fix(format!(
+						"add a [[terms]] entry named {} or remove it from the class",
+						term.get_ref()
+					)),
+				);
+			}
+		}
+	}
+
+	for term in &spans.terms {
+		if let Some(problem) = Term::name_problem(term.name.get_ref()) {
+			problems.push(
+				Problem::new(
+					file,
+					format!("The term name {} {}", term.name.get_ref(), problem),
+				)
+				.at(content, term.name.start())
+				.fix("rename it without slashes, colons or any of ~ ^ ? * ["),
+			);
+		}
+	}
+
+	for (key, value) in [
+		("view_with", &spans.view_with),
+		("open_with", &spans.open_with),
+	] {
+		if let Some(value) = value.as_ref().filter(|v| v.get_ref().is_empty()) {
+			problems.push(
+				Problem::new(file, format!("{} is empty", key))
+					.at(content, value.start())
+					.fix(format!(
+						"list a program and its arguments (e.g. {} = [\"code\"]) or remove the key",
+						key
+					)),
+			);
+		}
+	}
+
+	// engine replaced latexmk, which only still applies when engine isn't set
+	if let Some(latexmk) = &spans.latexmk {
+		problems.push(
+			Problem::new(file, "latexmk is deprecated")
+				.at(content, latexmk.start())
+				.fix(if *latexmk.get_ref() {
+					"replace it with engine = \"latexmk\""
+				} else {
+					"remove it"
+				}),
+		);
+	}
+
+	let keys = config
+		.type_outputs
+		.keys()
+		.map(|k| ("type_outputs", k))
+		.chain(
+			config
+				.classes
+				.iter()
+				.flat_map(|c| c.weights.keys().map(|k| ("weights", k))),
+		);
+	for (table, key) in keys {
+		if DocumentType::from_str(key).is_err() {
+			problems.push(
+				Problem::new(file, format!("{} in {} is not a document type", key, table))
+					.fix(format!("use one of {}", DocumentType::VARIANTS.join(", "))),
+			);
+		}
+	}
+	problems
+}
+
+fn template_problems(content: &str, config: &Config) -> Vec<Problem> {
+	let file = Path::new(files::CONFIG);
+	let branch_dir = Path::new(folders::TEMPLATES).join(folders::BRANCH_TEMPLATES);
+	let root_dir = Path::new(folders::TEMPLATES).join(folders::ROOT_TEMPLATES);
+	let spans: Spans = toml::from_str(content).unwrap_or_default();
+	let mut problems = Vec::new();
+
+	let branch_templates = match BranchTemplate::get_all() {
+		Ok(templates) => templates,
+		Err(err) => {
+			problems.push(
+				Problem::new(&branch_dir, format!("{:#}", err))
+					.fix("name branch templates <name><extension>.hbs, e.g. default.md.hbs"),
+			);
+			Vec::new()
+		}
+	};
+	let root_templates = RootTemplate::get_all().unwrap_or_default();
+	let root_format = |format: &Format| {
+		root_templates
+			.iter()
+			.filter(|r| {
+				Format::from_path(Path::new(&r.name)).unwrap_or_default()
+					== format.plugin().root_format()
+			})
+			.collect::<Vec<&RootTemplate>>()
+	};
+
+	let mut formats = vec![(
+		config.default_format.clone(),
+		spans.default_format.as_ref().map(|s| s.start()),
+	)];
+	for (class, class_spans) in config.classes.iter().zip(&spans.classes) {
+		let format = class
+			.format
+			.clone()
+			.unwrap_or_else(|| config.default_format.clone());
+		if let Some(name) = &class.branch_template {
+			if !branch_templates
+				.iter()
+				.any(|t| &t.name == name && t.format == format)
+			{
+				problems.push(
+					Problem::new(
+						file,
+						format!(
+							"{} uses the {} branch template {} which doesn't exist",
+							class.name, format, name
+						),
+					)
+					.at(
+						content,
+						class_spans
+							.branch_template
+							.as_ref()
+							.map_or(0, |s| s.start()),
+					)
+					.fix(format!(
+						"add {} or change branch_template",
+						branch_dir
+							.join(format!("{}{}", name, format.plugin().template_suffix()))
+							.display()
+					)),
+				);
+			}
+		}
+		if let Some(name) = &class.root_template {
+			if !root_format(&format).iter().any(|t| &t.name == name) {
+				problems.push(
+					Problem::new(
+						file,
+						format!(
+							"{} uses the root template {} which doesn't exist",
+							class.name, name
+						),
+					)
+					.at(
+						content,
+						class_spans.root_template.as_ref().map_or(0, |s| s.start()),
+					)
+					.fix(format!(
+						"add {} or change root_template",
+						root_dir.join(format!("{}.hbs", name)).display()
+					)),
+				);
+			}
+		}
+		if class.format.is_some() {
+			formats.push((format, class_spans.format.as_ref().map(|s| s.start())));
+		}
+	}
+
+	let mut seen = HashSet::new();
+	for (format, offset) in formats {
+		if !seen.insert(format.to_string()) {
+			continue;
+		}
+		let at = |problem: Problem| {
+			match offset {
+				Some(offset) => problem.at(content, offset),
+				None => problem,
+			}
+		};
+		if !branch_templates.iter().any(|t| t.format == format) {
+			problems.push(at(Problem::new(
+				file,
+				format!("No {} branch templates in {}", format, branch_dir.display()),
+			)
+			.fix(format!(
+				"add one such as {}",
+				branch_dir
+					.join(format!("default{}", format.plugin().template_suffix()))
+					.display()
+			))));
+		}
+		if root_format(&format).is_empty() {
+			problems.push(at(Problem::new(
+				file,
+				format!(
+					"No {} root templates in {} for {} branches",
+					format.plugin().root_format(),
+					root_dir.display(),
+					format
+				),
+			)
+			.fix(format!(
+				"add one such as {}",
+				root_dir
+					.join(format!(
+						"base{}.hbs",
+						format.plugin().root_format().extension()
+					))
+					.display()
+			))));
+		}
+	}
+	problems
+}
+
+// Branches that fail for the same reason are reported once
+fn branch_problems(config: &Config) -> Result<Vec<Problem>> {
+	let mut problems: Vec<(Problem, usize)> = Vec::new();
+	let mut seen: HashMap<String, usize> = HashMap::new();
+	let mut add = |key: String, problem: Problem| {
+		match seen.get(&key) {
+			Some(index) => problems[*index].1 += 1,
+			None => {
+				seen.insert(key, problems.len());
+				problems.push((problem, 0));
+			}
+		}
+	};
+
+	for path in Branch::paths(true)? {
+		match Branch::parse(path.clone(), config) {
+			Ok(branch) => {
+				if !branch.root_template.path.exists() {
+					add(
+						branch.root_template.path.display().to_string(),
+						Problem::new(
+							&path,
+							format!(
+								"Uses the root template {} which doesn't exist",
+								branch.root_template.name
+							),
+						)
+						.fix(format!(
+							"add {} or change root in the header",
+							branch.root_template.path.display()
+						)),
+					);
+				}
+			}
+			Err(err) => {
+				let fix = error::hint(&err).map(String::from);
+				let mut problem = Problem::new(&path, format!("{:#}", err));
+				problem.fix = fix.clone();
+				add(fix.unwrap_or_else(|| format!("{:#}", err)), problem);
+			}
+		}
+	}
+
+	Ok(problems
+		.into_iter()
+		.map(|(mut problem, others)| {
+			if others > 0 {
+				problem.message = format!(
+					"{} (and {} other {})",
+					problem.message,
+					others,
+					if others == 1 { "branch" } else { "branches" }
+				);
+			}
+			problem
+		})
+		.collect())
+}
+
+#[cfg(test)]
+mod test {
+	use std::path::Path;
+
+	use super::{config_problems, Problem};
+	use crate::conf::Config;
+
+	#[test]
+	fn problems() -> anyhow::Result<()> {
+		let content = [
+			"author = \"Matt Gleich\"",
+			"view_with = []",
+			"latexmk = true",
+			"",
+			"[type_outputs]",
+			"Essay = [\"docx\"]",
+			"",
+			"[[classes]]",
+			"name = \"AP Physics 2\"",
+			"teacher = \"Mr. Feynman\"",
+			"terms = [\"Fall 2022\"]",
+			"",
+			"[[classes]]",
+			"name = \"AP Physics 2\"",
+			"teacher = \"Mr. Feynman\"",
+			"",
+			"[[terms]]",
+			"name = \"2022/23\"",
+			"start = 2022-09-01",
+			"end = 2023-06-09",
+		]
+		.join("\n");
+		let problems = config_problems(&content, &toml::from_str::<Config>(&content)?);
+		let found: Vec<(Option<(usize, usize)>, &str)> = problems
+			.iter()
+			.map(|p| (p.position, p.message.as_str()))
+			.collect();
+		assert_eq!(
+			found,
+			vec![
+				(
+					Some((11, 10)),
+					"AP Physics 2 uses the term Fall 2022 which isn't defined"
+				),
+				(Some((14, 8)), "AP Physics 2 is defined more than once"),
+				(
+					Some((18, 8)),
+					"The term name 2022/23 can't be used as a folder name"
+				),
+				(Some((2, 13)), "view_with is empty"),
+				(Some((3, 11)), "latexmk is deprecated"),
+				(None, "Essay in type_outputs is not a document type"),
+			]
+		);
+
+		let err = toml::from_str::<Config>("author = \"Matt\"\nclasses = [").unwrap_err();
+		let problem = Problem::toml(Path::new("optic.toml"), &err);
+		assert_eq!(problem.position.map(|(line, _)| line), Some(2));
+		assert!(!problem.message.contains(" at line "));
+		Ok(())
+	}
+}